@@ -0,0 +1,142 @@
+use std::{
+    fs::{self, set_permissions},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    git_hook::{ApplyOptions, CommandConfig, GitHook},
+    hook_types::HookTypes,
+    identity,
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+    yml_parser::{self, Config},
+};
+
+/// Take over the unmanaged hook scripts of the current repo.
+///
+/// Every script in `.git/hooks` named after a known hook type is copied into
+/// `<data_dir>/scripts`, added to the config as a new hook, and replaced by the
+/// crab-hooks shim.
+pub fn adopt_hooks(
+    config_file: &Path,
+    data_dir: &Path,
+    glob_pattern: &[String],
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cd = identity::current_repo()?;
+    let cd_str = cd.to_str().expect("Failed to get current dir");
+    let repo_name = cd
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("repo")
+        .to_string();
+
+    // First check if the current directory is a git repo
+    let hooks_dir = PathBuf::from("./.git/hooks");
+    fs::read_dir("./.git/")?;
+
+    let mut config = if config_file.exists() {
+        yml_parser::read_config(config_file)?
+    } else {
+        Config::default()
+    };
+    let shim = ShimOptions {
+        template: config.load_shim_template()?,
+        config_file: Some(std::path::absolute(config_file)?),
+    };
+
+    let mut entries: Vec<PathBuf> = match fs::read_dir(&hooks_dir) {
+        Ok(dir) => dir.map_while(Result::ok).map(|e| e.path()).collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+
+    let mut adopted = Vec::new();
+    for path in entries {
+        if !path.is_file() {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if file_name.ends_with(".sample") {
+            continue;
+        }
+        // Adopting our own shim would make it dispatch to itself
+        if fs::read_to_string(&path).is_ok_and(|c| shim::is_shim(&c)) {
+            println!("Skipping {:?}, it is a crab-hooks shim", path);
+            continue;
+        }
+        let Ok(hook_type) = HookTypes::from_str(file_name) else {
+            println!("Skipping {:?}, not a known hook type", path);
+            continue;
+        };
+        if sql_config.check_if_hook_is_known(cd_str, &hook_type)? {
+            println!("Skipping {}, already managed by crab-hooks", hook_type);
+            continue;
+        }
+
+        let name = format!("{}-{}", repo_name, hook_type);
+        if config.hooks.iter().any(|h| h.name == name) {
+            return Err(format!(
+                "A hook named {} already exists in the config, aborting",
+                name
+            )
+            .into());
+        }
+
+        // Copy the script into the store
+        let scripts_dir = data_dir.join("scripts");
+        fs::create_dir_all(&scripts_dir)?;
+        let script_path = scripts_dir.join(&name);
+        fs::copy(&path, &script_path)?;
+        let mut permissions = fs::metadata(&script_path)?.permissions();
+        permissions.set_mode(0o755);
+        set_permissions(&script_path, permissions)?;
+
+        let hook = GitHook {
+            name: name.clone(),
+            command: CommandConfig {
+                cmd: script_path
+                    .to_str()
+                    .expect("Script path is not valid UTF-8")
+                    .to_string(),
+                ..CommandConfig::default()
+            },
+            glob_pattern: glob_pattern.to_vec(),
+            description: Some(format!(
+                "Adopted from {}",
+                cd.join(".git/hooks").join(file_name).display()
+            )),
+            // The script was written for git, which hands it its arguments
+            pass_args: true,
+            ..GitHook::default()
+        };
+        config.hooks.push(hook.clone());
+        adopted.push((hook, hook_type, path));
+    }
+
+    if adopted.is_empty() {
+        println!("No unmanaged hooks found to adopt");
+        return Ok(());
+    }
+
+    if let Some(parent) = config_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    yml_parser::write_config(config_file, &config)?;
+
+    // The original scripts now live in the store, so install the shim in their place
+    for (hook, hook_type, path) in adopted {
+        fs::remove_file(&path)?;
+        let options = ApplyOptions {
+            shim: shim.clone(),
+            ..ApplyOptions::default()
+        };
+        hook.apply_hook(&cd, &hook_type, &options, sql_config)?;
+        println!("Adopted {} as {}", hook_type, hook.name);
+    }
+    Ok(())
+}
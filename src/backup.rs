@@ -0,0 +1,128 @@
+use std::{
+    fs::{self, set_permissions, File, OpenOptions},
+    io::{self, ErrorKind},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    hook_types::HookTypes,
+    identity, shim,
+    sqllite::SqlLiteConfig,
+    time::{format_time, now},
+};
+
+/// Copy an unmanaged hook file into `<data_dir>/backups` and record it in SQLite
+pub fn backup_hook(
+    file_path: &Path,
+    repo: &str,
+    hook_type: &HookTypes,
+    data_dir: &Path,
+    sql_config: &SqlLiteConfig,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let created_at = now();
+
+    // One folder per repo. `_` is doubled before `/` becomes `_`, so two repo
+    // paths never share a folder
+    let repo_dir = repo
+        .trim_start_matches('/')
+        .replace('_', "__")
+        .replace('/', "_");
+    let backup_dir = data_dir.join("backups").join(repo_dir);
+    fs::create_dir_all(&backup_dir)?;
+
+    // Backups taken within the same second get a `.n` suffix, the file is
+    // created exclusively so a concurrent backup never overwrites this one
+    let (backup_path, mut backup_file) = create_new_file(&backup_dir, hook_type, created_at)?;
+    io::copy(&mut File::open(file_path)?, &mut backup_file)?;
+
+    sql_config.add_backup(
+        repo,
+        hook_type,
+        backup_path
+            .to_str()
+            .expect("Backup path is not valid UTF-8"),
+        created_at,
+    )?;
+    Ok(backup_path)
+}
+
+/// Create `<hook_type>-<created_at>`, or the first free `.n` suffix of it
+fn create_new_file(
+    dir: &Path,
+    hook_type: &HookTypes,
+    created_at: i64,
+) -> Result<(PathBuf, File), Box<dyn std::error::Error>> {
+    let mut n = 0;
+    loop {
+        let name = match n {
+            0 => format!("{}-{}", hook_type, created_at),
+            n => format!("{}-{}.{}", hook_type, created_at, n),
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// List the backups of the current repo, or put one of them back in place.
+///
+/// Without an `id` the backups are only listed; `latest` picks the newest one.
+pub fn restore(
+    hook_type: &HookTypes,
+    id: Option<i64>,
+    latest: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cd = identity::current_repo()?
+        .to_str()
+        .expect("Failed to get current dir")
+        .to_string();
+
+    // First check if the current directory is a git repo
+    fs::read_dir("./.git/")?;
+
+    let backups = sql_config.list_backups(&cd, hook_type)?;
+    if backups.is_empty() {
+        return Err(format!("No backups found for {} in this repo", hook_type).into());
+    }
+
+    let backup = match (id, latest) {
+        (Some(id), _) => backups
+            .iter()
+            .find(|b| b.id == id)
+            .ok_or_else(|| format!("No backup with id {} for {} in this repo", id, hook_type))?,
+        (None, true) => &backups[0],
+        (None, false) => {
+            println!("Backups of {} for {}:", hook_type, cd);
+            for b in &backups {
+                println!(
+                    " - {}: {} (created {})",
+                    b.id,
+                    b.path,
+                    format_time(b.created_at)
+                );
+            }
+            return Ok(());
+        }
+    };
+
+    if sql_config.check_if_hook_is_known(&cd, hook_type)? {
+        return Err(format!(
+            "{} still has managed hooks, please remove those before restoring",
+            hook_type
+        )
+        .into());
+    }
+
+    let file_path = shim::hook_path(Path::new("."), hook_type);
+    fs::copy(&backup.path, &file_path)?;
+    let mut permissions = fs::metadata(&file_path)?.permissions();
+    permissions.set_mode(0o755);
+    set_permissions(&file_path, permissions)?;
+    println!("Restored {} from {}", hook_type, backup.path);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::sqllite::SqlLiteConfig;
+
+/// Collect the repos a bulk command should act on.
+///
+/// Paths are canonicalized, so they match the keys stored in `repo_hooks`.
+/// `all_known` takes every repo registered by `scan` and every repo with hooks installed.
+pub fn resolve_repos(
+    repos: &[PathBuf],
+    repos_from: Option<&Path>,
+    all_known: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = repos.to_vec();
+    if let Some(file) = repos_from {
+        let content = fs::read_to_string(file)
+            .map_err(|e| format!("Cannot read repo list {:?}: {}", file, e))?;
+        paths.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(PathBuf::from),
+        );
+    }
+
+    let mut resolved = Vec::new();
+    for path in paths {
+        let canonical =
+            fs::canonicalize(&path).map_err(|e| format!("Cannot find repo {:?}: {}", path, e))?;
+        resolved.push(canonical);
+    }
+    if all_known {
+        // Known repos may be gone already, those show up as failures in the report
+        resolved.extend(
+            sql_config
+                .list_registered_repos()?
+                .into_iter()
+                .map(PathBuf::from),
+        );
+        resolved.extend(
+            sql_config
+                .list_installs()?
+                .into_iter()
+                .map(|(repo, _)| PathBuf::from(repo)),
+        );
+    }
+    resolved.sort();
+    resolved.dedup();
+    Ok(resolved)
+}
+
+/// Run `f` on every repo and print a per-repo report.
+///
+/// Stops at the first failure unless `continue_on_error` is set, and fails if any repo failed.
+pub fn for_each_repo<F>(
+    repos: &[PathBuf],
+    continue_on_error: bool,
+    mut f: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut(&Path) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut succeeded = 0;
+    let mut failed = Vec::new();
+    for repo in repos {
+        println!("== {}", repo.display());
+        match f(repo) {
+            Ok(()) => succeeded += 1,
+            Err(e) => {
+                eprintln!("Failed for {}: {}", repo.display(), e);
+                failed.push(repo);
+                if !continue_on_error {
+                    break;
+                }
+            }
+        }
+    }
+
+    let skipped = repos.len() - succeeded - failed.len();
+    println!(
+        "{} succeeded, {} failed, {} not attempted",
+        succeeded,
+        failed.len(),
+        skipped
+    );
+    for repo in &failed {
+        println!("  failed: {}", repo.display());
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed in {} of {} repositories", failed.len(), repos.len()).into())
+    }
+}
@@ -0,0 +1,152 @@
+use git2::{ObjectType, Oid, Repository, StatusOptions};
+use globset::{GlobBuilder, GlobSetBuilder};
+use std::{collections::BTreeMap, fs};
+
+use crate::{git_hook::GitHook, hook_types::HookTypes, sqllite::SqlLiteConfig};
+
+/// Set to anything to always run the hooks, like `--no-cache`
+pub const NO_CACHE_ENV: &str = "CRAB_HOOKS_NO_CACHE";
+
+/// What a cached result is keyed by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    pub repo: String,
+    /// See `GitHook::config_hash`
+    pub config_hash: String,
+    /// The content the hook looks at, see `content_key`
+    pub content_key: String,
+    /// What git handed the hook, see `input_key`
+    pub input: String,
+}
+
+impl CacheKey {
+    /// The content and input together, what a run records so a hook is only
+    /// called flaky when it passed and failed on the same of both
+    pub fn run_key(&self) -> String {
+        format!("{}+input:{}", self.content_key, self.input)
+    }
+}
+
+/// Whether the hook looks at the whole repo rather than some files
+fn is_whole_repo(hook: &GitHook) -> bool {
+    hook.glob_pattern.iter().any(|p| p == "**" || p == "**/*")
+}
+
+/// Identify the content a hook would run on.
+///
+/// For a whole-repo hook on a clean checkout this is the tree of HEAD. Otherwise
+/// it is a hash of the paths and blob ids of the files matching the hook's
+/// glob patterns, as they are in the working tree, so staged, unstaged and
+/// untracked changes all count.
+pub fn content_key(
+    repo: &Repository,
+    hook: &GitHook,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("not a workdir"))?;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts))?;
+
+    if is_whole_repo(hook) && statuses.is_empty() {
+        if let Ok(tree) = repo.head().and_then(|h| h.peel_to_tree()) {
+            return Ok(format!("tree:{}", tree.id()));
+        }
+    }
+
+    let mut globs = GlobSetBuilder::new();
+    for pattern in &hook.glob_pattern {
+        globs.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    let matcher = globs.build()?;
+
+    // Start from the index, it has the blob ids of every tracked file
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    for entry in repo.index()?.iter() {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        if matcher.is_match(&path) {
+            files.insert(path, entry.id.to_string());
+        }
+    }
+    // Then hash whatever differs in the working tree
+    for status in statuses.iter() {
+        let Some(path) = status.path() else {
+            continue;
+        };
+        if !matcher.is_match(path) {
+            continue;
+        }
+        let full_path = workdir.join(path);
+        if full_path.is_file() {
+            let oid = Oid::hash_file(ObjectType::Blob, &full_path)?;
+            files.insert(path.to_string(), oid.to_string());
+        } else {
+            files.remove(path);
+        }
+    }
+
+    let mut listing = String::new();
+    for (path, oid) in &files {
+        listing.push_str(&format!("{} {}\n", oid, path));
+    }
+    let oid = Oid::hash_object(ObjectType::Blob, listing.as_bytes())?;
+    Ok(format!("files:{}", oid))
+}
+
+/// Whether git hands the hook a message file as its first argument. The path is
+/// always the same, like `.git/COMMIT_EDITMSG`, the message is in the file.
+fn takes_message_file(hook_type: Option<&HookTypes>) -> bool {
+    matches!(
+        hook_type,
+        Some(HookTypes::PrepareCommitMsg | HookTypes::CommitMsg | HookTypes::ApplyPatchMsg)
+    )
+}
+
+/// Identify what git handed a hook: its type, arguments, the content of its
+/// message file and stdin. A commit-msg hook passing on one message says nothing
+/// about the next.
+pub fn input_key(
+    hook_type: Option<&HookTypes>,
+    hook_args: &[String],
+    stdin: Option<&[u8]>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut bytes = serde_json::to_vec(&(hook_type.map(|t| t.to_string()), hook_args))?;
+    if let Some(file) = hook_args.first().filter(|_| takes_message_file(hook_type)) {
+        let message = fs::read(file)?;
+        bytes.push(b'\n');
+        bytes.extend_from_slice(Oid::hash_object(ObjectType::Blob, &message)?.as_bytes());
+    }
+    bytes.push(b'\n');
+    bytes.extend_from_slice(stdin.unwrap_or_default());
+    Ok(Oid::hash_object(ObjectType::Blob, &bytes)?.to_string())
+}
+
+/// The cache key of a hook in the repo of the current directory
+pub fn key(
+    hook: &GitHook,
+    hook_type: Option<&HookTypes>,
+    hook_args: &[String],
+    stdin: Option<&[u8]>,
+) -> Result<CacheKey, Box<dyn std::error::Error>> {
+    let repo = Repository::discover(".")?;
+    let repo_path = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("not a workdir"))?
+        .canonicalize()?;
+    Ok(CacheKey {
+        repo: repo_path.to_string_lossy().to_string(),
+        config_hash: hook.config_hash()?,
+        content_key: content_key(&repo, hook)?,
+        input: input_key(hook_type, hook_args, stdin)?,
+    })
+}
+
+/// Forget every cached result
+pub fn clear(sql_config: &SqlLiteConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let removed = sql_config.clear_cached_passes()?;
+    println!("Removed {} cached result(s)", removed);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use crate::{
+    hook_types::HookTypes,
+    sqllite::{RepoHook, SqlLiteConfig},
+    time::{format_time, now},
+};
+
+/// Switch off an installed hook in a repo, for one hook type or every type it is installed as.
+/// `until` must be in the future.
+pub fn disable(
+    repo: &Path,
+    key: &str,
+    hook_type: Option<&HookTypes>,
+    until: Option<i64>,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(until) = until.filter(|&until| until <= now()) {
+        return Err(format!("{} is not in the future", format_time(until)).into());
+    }
+    let repo_str = repo.to_string_lossy();
+    let changed = sql_config.set_hook_disabled(&repo_str, key, hook_type, true, until)?;
+    if changed == 0 {
+        return Err(not_installed(key, hook_type));
+    }
+    match until {
+        Some(until) => println!("Disabled {} until {}", key, format_time(until)),
+        None => println!(
+            "Disabled {}, run `crab-hooks enable {}` to turn it back on",
+            key, key
+        ),
+    }
+    Ok(())
+}
+
+/// Turn a disabled hook back on
+pub fn enable(
+    repo: &Path,
+    key: &str,
+    hook_type: Option<&HookTypes>,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo_str = repo.to_string_lossy();
+    let changed = sql_config.set_hook_disabled(&repo_str, key, hook_type, false, None)?;
+    if changed == 0 {
+        return Err(not_installed(key, hook_type));
+    }
+    println!("Enabled {}", key);
+    Ok(())
+}
+
+fn not_installed(key: &str, hook_type: Option<&HookTypes>) -> Box<dyn std::error::Error> {
+    match hook_type {
+        Some(t) => format!("{} is not installed as {} in this repo", key, t).into(),
+        None => format!("{} is not installed in this repo", key).into(),
+    }
+}
+
+/// Whether `key` is switched off in a repo right now, as one hook type or as every
+/// type it is installed as
+pub fn is_disabled(
+    repo: &str,
+    key: &str,
+    hook_type: Option<&HookTypes>,
+    sql_config: &SqlLiteConfig,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let now = now();
+    let hook_type = hook_type.map(|t| t.to_string());
+    let installs: Vec<RepoHook> = sql_config
+        .list_all_repo_hooks()?
+        .into_iter()
+        .filter(|r| r.repo == repo && r.name == key)
+        .filter(|r| hook_type.as_ref().is_none_or(|t| &r.hook_type == t))
+        .collect();
+    Ok(!installs.is_empty() && installs.iter().all(|r| r.is_disabled(now)))
+}
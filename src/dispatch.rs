@@ -0,0 +1,175 @@
+use std::{
+    io::{IsTerminal, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    disable,
+    git_hook::{self, GitHook, RunOptions, RunOutcome},
+    history,
+    hook_types::HookTypes,
+    identity, logs, notes, shim,
+    sqllite::SqlLiteConfig,
+    yml_parser,
+};
+
+/// The hooks installed in `repo` as `hook_type`, in the order they should run
+pub fn installed_hooks(
+    config_file: PathBuf,
+    repo: &str,
+    hook_type: &HookTypes,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<GitHook>, Box<dyn std::error::Error>> {
+    let names = sql_config.list_repo_hooks(repo, hook_type)?;
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let config = yml_parser::read_file(config_file)?;
+    let mut hooks = Vec::new();
+    for name in names {
+        match config.iter().find(|h| h.key() == name) {
+            Some(hook) => hooks.push(hook.clone()),
+            None => {
+                return Err(format!(
+                    "Hook {} is installed as {} but missing from the config",
+                    name, hook_type
+                )
+                .into())
+            }
+        }
+    }
+    // Stable sort, so equal priorities keep their install order
+    hooks.sort_by_key(|h| h.priority.unwrap_or(0));
+    Ok(hooks)
+}
+
+/// Validate the hooks that will run, leaving out those skipped on request or
+/// disabled, so skipping a broken hook gets past it
+pub fn validate_runnable(
+    config_file: &Path,
+    repo: &str,
+    hook_type: &HookTypes,
+    hooks: &[GitHook],
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut names = Vec::new();
+    for hook in hooks {
+        if hook.requested_skip().is_none()
+            && !disable::is_disabled(repo, hook.key(), Some(hook_type), sql_config)?
+        {
+            names.push(hook.key());
+        }
+    }
+    if names.is_empty() {
+        return Ok(());
+    }
+    yml_parser::test_hooks(config_file.to_path_buf(), &names)
+}
+
+/// Entry point of the shim: run every hook installed for `hook_type` in the current repo.
+/// Unless `validate` is false, the hooks that will run are validated first.
+pub fn dispatch(
+    hook_type: &HookTypes,
+    hook_args: &[String],
+    config_file: PathBuf,
+    use_cache: bool,
+    validate: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    shim::check_shim_version();
+
+    let cd = identity::current_repo()?
+        .to_str()
+        .expect("Failed to get current dir")
+        .to_string();
+
+    // The shim is there but nothing is recorded here, the repo may have been moved
+    if !sql_config.check_if_hook_is_known(&cd, hook_type)? {
+        // The scripts keep handing over this config, even when it can't be read right now
+        let shim = shim::ShimOptions::load(&config_file).unwrap_or_else(|_| shim::ShimOptions {
+            template: None,
+            config_file: std::path::absolute(&config_file).ok(),
+        });
+        if let Err(e) = identity::auto_relocate(Path::new(&cd), &shim, sql_config) {
+            eprintln!("crab-hooks: {}", e);
+        }
+    }
+
+    let hooks = match installed_hooks(config_file.clone(), &cd, hook_type, sql_config) {
+        Ok(hooks) => hooks,
+        // Skipping everything must work even when the config does not
+        Err(e) if git_hook::all_skipped() => {
+            println!("Skipping every hook, {}=0 ({})", git_hook::ENABLED_ENV, e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if validate {
+        validate_runnable(&config_file, &cd, hook_type, &hooks, sql_config)?;
+    }
+
+    // Git passes data like the pushed refs on stdin, every hook needs its own copy
+    let mut input = Vec::new();
+    let stdin = std::io::stdin();
+    let input = if stdin.is_terminal() {
+        None
+    } else {
+        stdin.lock().read_to_end(&mut input)?;
+        Some(input)
+    };
+
+    if !hooks.is_empty() {
+        if let Err(e) = logs::start_last_run(&hook_type.to_string()) {
+            eprintln!("crab-hooks: could not write last-run.log: {}", e);
+        }
+    }
+
+    let options = RunOptions {
+        hook_type: Some(hook_type.clone()),
+        use_cache,
+    };
+    let mut passed = Vec::new();
+    let mut proven = 0;
+    let mut skipped = Vec::new();
+    for hook in &hooks {
+        if disable::is_disabled(&cd, hook.key(), Some(hook_type), sql_config)? {
+            println!("Skipping {}, disabled in this repo", hook.name);
+            sql_config.add_skipped_run(hook.key())?;
+            history::save_skip(hook, Some(hook_type), sql_config)?;
+            skipped.push(hook.name.as_str());
+            continue;
+        }
+        if *hook_type == HookTypes::PrePush && notes::passed_on_head(Path::new(&cd), hook) {
+            println!(
+                "Skipping {}, a git note shows it passed on this tree",
+                hook.name
+            );
+            proven += 1;
+            continue;
+        }
+        match hook.run(hook_args, input.as_deref(), &options, sql_config)? {
+            RunOutcome::Passed | RunOutcome::Cached => passed.push(hook),
+            RunOutcome::NoMatch => (),
+            RunOutcome::Skipped(_) => skipped.push(hook.name.as_str()),
+        }
+    }
+    if !skipped.is_empty() {
+        println!(
+            "crab-hooks: {} passed, {} skipped ({})",
+            passed.len() + proven,
+            skipped.len(),
+            skipped.join(", ")
+        );
+    }
+
+    // Provenance is best effort, it never fails the git command
+    if let Err(e) = notes::record(Path::new(&cd), hook_type, &passed) {
+        eprintln!(
+            "crab-hooks: could not record the results in git notes: {}",
+            e
+        );
+    }
+    history::trim(&config_file, sql_config);
+    Ok(())
+}
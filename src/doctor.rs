@@ -0,0 +1,432 @@
+use git2::{ConfigLevel, Repository};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    backup, git_hook,
+    hook_types::HookTypes,
+    identity,
+    shim::{self, ShimOptions},
+    sqllite::{SqlLiteConfig, SCHEMA_VERSION},
+    yml_parser::{self, Config},
+};
+
+/// Something `doctor` can repair by itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// Write the hook script again, backing up whatever replaced it
+    RewriteShim { repo: PathBuf, hook_type: HookTypes },
+    /// Remove `core.hooksPath` from the repo's own git config
+    UnsetHooksPath { repo: PathBuf },
+    /// Uninstall a hook that is gone from the config
+    Uninstall {
+        repo: PathBuf,
+        hook_type: HookTypes,
+        name: String,
+    },
+    /// Move the rows of a hook that was given an id from its name to the id
+    AdoptId { name: String, id: String },
+    /// Create the database, or bring its schema up to date
+    MigrateDatabase { path: PathBuf },
+}
+
+/// One failed check
+#[derive(Debug)]
+pub struct Problem {
+    pub message: String,
+    /// What to do about it
+    pub hint: String,
+    pub fix: Option<Fix>,
+}
+
+/// The config parses and passes `test`
+pub fn check_config(config_file: &Path) -> (Option<Config>, Vec<Problem>) {
+    let config = match yml_parser::read_config(config_file) {
+        Ok(config) => config,
+        Err(e) => {
+            let problem = Problem {
+                message: format!("Cannot read {}: {}", config_file.display(), e),
+                hint: "fix the config file, or pass the right one with --config-file".into(),
+                fix: None,
+            };
+            return (None, vec![problem]);
+        }
+    };
+    let mut problems = Vec::new();
+    if let Err(e) = yml_parser::test_config(config_file.to_path_buf()) {
+        problems.push(Problem {
+            message: e.to_string(),
+            hint: "fix the errors above, `crab-hooks test` checks the config again".into(),
+            fix: None,
+        });
+    }
+    (Some(config), problems)
+}
+
+/// `hooks.db` exists, opens and its schema is the one this binary expects.
+///
+/// The database is opened read-only, nothing is created or migrated until the
+/// fix is applied. No connection is returned when the schema is outdated, the
+/// other checks would trip over the missing tables.
+pub fn check_database(db_path: &Path) -> (Option<SqlLiteConfig>, Vec<Problem>) {
+    let migrate = Fix::MigrateDatabase {
+        path: db_path.to_path_buf(),
+    };
+    if !db_path.exists() {
+        let problem = Problem {
+            message: format!("{} does not exist", db_path.display()),
+            hint: "installing a hook creates it, or run `crab-hooks doctor --fix`".into(),
+            fix: Some(migrate),
+        };
+        return (None, vec![problem]);
+    }
+    let sql_config = match SqlLiteConfig::open_read_only(&db_path.to_string_lossy()) {
+        Ok(sql_config) => sql_config,
+        Err(e) => {
+            let problem = Problem {
+                message: format!("Cannot open {}: {}", db_path.display(), e),
+                hint: format!(
+                    "check the file permissions, or move {} aside to start over",
+                    db_path.display()
+                ),
+                fix: None,
+            };
+            return (None, vec![problem]);
+        }
+    };
+    let mut problems = Vec::new();
+    match sql_config.schema_version() {
+        Ok(version) if version == SCHEMA_VERSION => (),
+        Ok(version) if version < SCHEMA_VERSION => {
+            let problem = Problem {
+                message: format!(
+                    "{} has schema version {}, this crab-hooks expects {}",
+                    db_path.display(),
+                    version,
+                    SCHEMA_VERSION
+                ),
+                hint: "the database was written by an older crab-hooks, `crab-hooks doctor --fix` migrates it".into(),
+                fix: Some(migrate),
+            };
+            return (None, vec![problem]);
+        }
+        Ok(version) => problems.push(Problem {
+            message: format!(
+                "{} has schema version {}, this crab-hooks expects {}",
+                db_path.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+            hint: "the database was written by a newer crab-hooks, please upgrade".into(),
+            fix: None,
+        }),
+        Err(e) => problems.push(Problem {
+            message: format!("Cannot read the schema of {}: {}", db_path.display(), e),
+            hint: format!("move {} aside to start over", db_path.display()),
+            fix: None,
+        }),
+    }
+    (Some(sql_config), problems)
+}
+
+/// The hook scripts of every type installed in `repo`, and `core.hooksPath`
+pub fn check_repo(
+    repo: &Path,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<Problem>, Box<dyn std::error::Error>> {
+    let repo_str = repo.to_string_lossy();
+    let mut problems = Vec::new();
+
+    let mut hook_types: Vec<String> = sql_config
+        .list_all_repo_hooks()?
+        .into_iter()
+        .filter(|r| r.repo == repo_str)
+        .map(|r| r.hook_type)
+        .collect();
+    hook_types.dedup();
+
+    for hook_type in hook_types {
+        let Ok(hook_type) = HookTypes::from_str(&hook_type) else {
+            continue;
+        };
+        let file_path = shim::hook_path(repo, &hook_type);
+        let rewrite = Some(Fix::RewriteShim {
+            repo: repo.to_path_buf(),
+            hook_type: hook_type.clone(),
+        });
+        let Ok(content) = fs::read_to_string(&file_path) else {
+            problems.push(Problem {
+                message: format!("{} is missing", file_path.display()),
+                hint: "run `crab-hooks repair` to write it again".into(),
+                fix: rewrite,
+            });
+            continue;
+        };
+        if !shim::is_shim(&content) {
+            problems.push(Problem {
+                message: format!(
+                    "{} was replaced and no longer runs crab-hooks",
+                    file_path.display()
+                ),
+                hint: "run `crab-hooks doctor --fix` to back it up and write the hook script again"
+                    .into(),
+                fix: rewrite,
+            });
+            continue;
+        }
+        let executable = fs::metadata(&file_path)
+            .map(|m| m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if !executable {
+            problems.push(Problem {
+                message: format!("{} is not executable", file_path.display()),
+                hint: format!("run `chmod +x {}`", file_path.display()),
+                fix: rewrite.clone(),
+            });
+        }
+        if let Some(binary) = shim::recorded_binary(&content) {
+            if !Path::new(binary).exists() {
+                problems.push(Problem {
+                    message: format!(
+                        "{} falls back to {}, which does not exist",
+                        file_path.display(),
+                        binary
+                    ),
+                    hint: "the hooks only run while crab-hooks is on PATH, run `crab-hooks repair` to record the current binary".into(),
+                    fix: rewrite,
+                });
+            }
+        }
+    }
+
+    if let Some(hooks_path) = hooks_path(repo)? {
+        problems.push(Problem {
+            message: format!(
+                "core.hooksPath is set to {}, git ignores the scripts in .git/hooks",
+                hooks_path.display()
+            ),
+            hint: "run `git config --unset core.hooksPath` (or with --global where it is set)"
+                .into(),
+            fix: Some(Fix::UnsetHooksPath {
+                repo: repo.to_path_buf(),
+            }),
+        });
+    }
+    Ok(problems)
+}
+
+/// Where `core.hooksPath` points, when it points anywhere but `.git/hooks`
+fn hooks_path(repo: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let git_repo = Repository::open(repo)?;
+    let Ok(path) = git_repo.config()?.get_path("core.hooksPath") else {
+        return Ok(None);
+    };
+    let path = if path.is_relative() {
+        repo.join(path)
+    } else {
+        path
+    };
+    let default = repo.join(".git/hooks");
+    let same = match (fs::canonicalize(&path), fs::canonicalize(&default)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => path == default,
+    };
+    Ok((!same).then_some(path))
+}
+
+/// Every hook installed anywhere still exists in the config
+pub fn check_hook_names(
+    config: &Config,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<Problem>, Box<dyn std::error::Error>> {
+    let mut problems = Vec::new();
+    let added_ids = git_hook::added_ids(config, sql_config)?;
+    for hook in &added_ids {
+        problems.push(Problem {
+            message: format!(
+                "{} was given the id {}, but its installs are still recorded under {}",
+                hook.name,
+                hook.key(),
+                hook.name
+            ),
+            hint: "any other crab-hooks command moves them over, so does `crab-hooks doctor --fix`"
+                .into(),
+            fix: Some(Fix::AdoptId {
+                name: hook.name.clone(),
+                id: hook.key().to_string(),
+            }),
+        });
+    }
+    for row in sql_config.list_all_repo_hooks()? {
+        if config.find_hook_by_key(&row.name).is_some()
+            || added_ids.iter().any(|h| h.name == row.name)
+        {
+            continue;
+        }
+        let Ok(hook_type) = HookTypes::from_str(&row.hook_type) else {
+            continue;
+        };
+        problems.push(Problem {
+            message: format!(
+                "{} is installed as {} in {}, but is missing from the config",
+                row.name, row.hook_type, row.repo
+            ),
+            hint: format!(
+                "add {} back to the config, or uninstall it with `crab-hooks doctor --fix`",
+                row.name
+            ),
+            fix: Some(Fix::Uninstall {
+                repo: PathBuf::from(&row.repo),
+                hook_type,
+                name: row.name,
+            }),
+        });
+    }
+    Ok(problems)
+}
+
+/// Carry out a fix
+pub fn apply_fix(
+    fix: &Fix,
+    shim: &ShimOptions,
+    data_dir: &Path,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match fix {
+        Fix::MigrateDatabase { path } => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            SqlLiteConfig::new(&path.to_string_lossy())?;
+            Ok(())
+        }
+        Fix::AdoptId { name, id } => git_hook::adopt_id(name, id, shim, sql_config),
+        Fix::RewriteShim { repo, hook_type } => {
+            let file_path = shim::hook_path(repo, hook_type);
+            let replaced = fs::read_to_string(&file_path).is_ok_and(|c| !shim::is_shim(&c));
+            if replaced {
+                let backup_path = backup::backup_hook(
+                    &file_path,
+                    &repo.to_string_lossy(),
+                    hook_type,
+                    data_dir,
+                    sql_config,
+                )?;
+                println!("  backup saved to {}", backup_path.display());
+            }
+            shim::write_shim(repo, hook_type, shim, sql_config)
+        }
+        Fix::UnsetHooksPath { repo } => {
+            let mut local = Repository::open(repo)?
+                .config()?
+                .open_level(ConfigLevel::Local)?;
+            local.remove("core.hooksPath").map_err(|_| {
+                "core.hooksPath is not set in this repo, unset it in your global git config instead"
+            })?;
+            Ok(())
+        }
+        Fix::Uninstall {
+            repo,
+            hook_type,
+            name,
+        } => {
+            if repo.join(".git").is_dir() {
+                git_hook::uninstall_hook(name, repo, hook_type, shim, sql_config)
+            } else {
+                sql_config.remove_hook(&repo.to_string_lossy(), hook_type, name)
+            }
+        }
+    }
+}
+
+/// The database opened for writing, on the first call
+fn writable_database<'a>(
+    writable: &'a mut Option<SqlLiteConfig>,
+    db_path: &Path,
+) -> Result<&'a SqlLiteConfig, Box<dyn std::error::Error>> {
+    if writable.is_none() {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        *writable = Some(SqlLiteConfig::new(&db_path.to_string_lossy())?);
+    }
+    Ok(writable.as_ref().expect("opened above"))
+}
+
+/// Run every check, print the problems with a hint each, and fix them when asked.
+///
+/// The database is opened here rather than by the caller, so a broken one is
+/// reported like any other problem. It is only opened for writing, which creates
+/// and migrates it, once a fix is applied.
+pub fn doctor(
+    config_file: &Path,
+    db_path: &Path,
+    data_dir: &Path,
+    fix: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (config, mut problems) = check_config(config_file);
+    let (sql_config, db_problems) = check_database(db_path);
+    problems.extend(db_problems);
+
+    if let Some(sql_config) = &sql_config {
+        let cd = identity::current_repo()?;
+        if cd.join(".git").is_dir() {
+            problems.extend(check_repo(&cd, sql_config)?);
+        } else {
+            println!("Not inside a git repository, skipping the repo checks");
+        }
+        if let Some(config) = &config {
+            problems.extend(check_hook_names(config, sql_config)?);
+        }
+    }
+
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+
+    let shim = ShimOptions {
+        template: match &config {
+            Some(config) => config.load_shim_template().unwrap_or_default(),
+            None => None,
+        },
+        config_file: std::path::absolute(config_file).ok(),
+    };
+    let mut writable = None;
+    let mut remaining = 0;
+    for problem in &problems {
+        println!("- {}", problem.message);
+        match &problem.fix {
+            Some(f) if fix => match writable_database(&mut writable, db_path)
+                .and_then(|sql_config| apply_fix(f, &shim, data_dir, sql_config))
+            {
+                Ok(()) => println!("  fixed"),
+                Err(e) => {
+                    println!("  could not fix it: {}", e);
+                    println!("  hint: {}", problem.hint);
+                    remaining += 1;
+                }
+            },
+            _ => {
+                println!("  hint: {}", problem.hint);
+                remaining += 1;
+            }
+        }
+    }
+
+    if remaining == 0 {
+        return Ok(());
+    }
+    let fixable = problems.iter().filter(|p| p.fix.is_some()).count();
+    if !fix && fixable > 0 {
+        println!(
+            "{} of these can be fixed with `crab-hooks doctor --fix`",
+            fixable
+        );
+    }
+    Err(format!("{} problem(s) found", remaining).into())
+}
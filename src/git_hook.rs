@@ -1,29 +1,110 @@
-use git2::{DiffOptions, Repository, StatusOptions};
+use git2::{DiffOptions, ObjectType, Oid, Repository, StatusOptions};
 use globset::GlobBuilder;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, set_permissions, OpenOptions},
-    io::{BufRead, BufReader, Write},
-    os::unix::fs::PermissionsExt,
-    path::PathBuf,
-    process::Command,
+    fs,
+    io::{ErrorKind, Write},
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    str::FromStr,
+    time::{Duration, Instant},
 };
 
-use crate::{hook_types::HookTypes, sqllite::SqlLiteConfig};
+use crate::{
+    backup, cache,
+    history::{self, RunStatus},
+    hook_types::HookTypes,
+    identity,
+    logs::{self, Captured, Tee},
+    shim::{self, ShimOptions},
+    sqllite::{RunRecord, SqlLiteConfig},
+    time,
+    yml_parser::{self, Config},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CommandConfig {
     pub cmd: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub directory: Option<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GitHook {
     pub name: String,
+    /// Immutable key of the hook in the database, so renaming it keeps its installs
+    /// and stats. Defaults to the name; once installed it must never change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub command: CommandConfig,
     pub glob_pattern: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Hooks of the same type run in ascending priority, then in install order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Seconds the command may run before it is killed and the hook fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Times a failure is retried, only once the hook is known to be flaky:
+    /// it passed and failed on the same content recently
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Append the arguments git hands the hook, like the message file of a
+    /// commit-msg hook or the remote of a pre-push hook, to the command
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pass_args: bool,
+}
+
+/// Set to `0` to skip every hook
+pub const ENABLED_ENV: &str = "CRAB_HOOKS";
+
+/// Whether `CRAB_HOOKS=0` asks to skip every hook
+pub fn all_skipped() -> bool {
+    std::env::var(ENABLED_ENV).is_ok_and(|v| v.trim() == "0")
+}
+
+/// Comma separated names of hooks to skip
+pub const SKIP_ENV: &str = "CRAB_HOOKS_SKIP";
+
+/// Git config key listing hooks to skip in a repo, comma separated or repeated
+pub const SKIP_GIT_CONFIG: &str = "crabhooks.skip";
+
+/// What happened when a hook was run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+    Passed,
+    /// None of the changed files matched the glob patterns
+    NoMatch,
+    /// Skipped on request, with the setting that asked for it
+    Skipped(String),
+    /// Already passed on the same content, see `cache`
+    Cached,
+}
+
+/// How `GitHook::run` runs a hook
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// The hook type being run, recorded in the history; `None` for `crab-hooks run`
+    pub hook_type: Option<HookTypes>,
+    /// Skip the hook when it already passed on the same content, see `cache`
+    pub use_cache: bool,
+}
+
+/// How `apply_hook` treats an existing hook file that crab-hooks does not manage
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Keep the existing hook as `<type>.legacy` and run it first
+    pub chain: bool,
+    /// Overwrite the existing hook after backing it up
+    pub force: bool,
+    /// Where backups are stored
+    pub data_dir: PathBuf,
+    /// How the hook script is written
+    pub shim: ShimOptions,
 }
 
 impl std::fmt::Display for GitHook {
@@ -39,7 +120,38 @@ impl std::fmt::Display for GitHook {
     }
 }
 
+/// Add a run to `last-run.log`, a warning is all a failure to do so is worth
+fn write_last_run(record: &RunRecord, captured: &Captured) {
+    if let Err(e) = logs::append_last_run(record, captured) {
+        eprintln!("crab-hooks: could not write last-run.log: {}", e);
+    }
+}
+
+/// Wait for `child`, killing it once `limit` has passed. `None` when it was killed.
+fn wait_with_timeout(
+    child: &mut Child,
+    limit: Duration,
+) -> Result<Option<ExitStatus>, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if started.elapsed() >= limit {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
 impl GitHook {
+    /// What the database knows this hook as, its `id` or else its name
+    pub fn key(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.name)
+    }
+
     fn find_changed_or_to_be_pushed_files(
         &self,
     ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
@@ -125,156 +237,269 @@ impl GitHook {
         false
     }
 
-    pub fn run(&self, sql_config: &SqlLiteConfig) -> Result<(), Box<dyn std::error::Error>> {
+    /// Stable hash of the settings that decide what the hook does when it runs,
+    /// so editing its name, description or priority keeps the cached results
+    pub fn config_hash(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let bytes = serde_json::to_vec(&(
+            &self.command,
+            &self.glob_pattern,
+            self.timeout,
+            self.pass_args,
+        ))?;
+        Ok(Oid::hash_object(ObjectType::Blob, &bytes)?.to_string())
+    }
+
+    /// Why the user asked to skip this hook, if they did: `CRAB_HOOKS=0`, the hook
+    /// named in `CRAB_HOOKS_SKIP`, or in the repo's `crabhooks.skip` git config
+    pub fn requested_skip(&self) -> Option<String> {
+        if all_skipped() {
+            return Some(format!("{}=0", ENABLED_ENV));
+        }
+        let names_this = |list: &str| {
+            list.split(',')
+                .map(str::trim)
+                .any(|n| n == self.name || n == self.key())
+        };
+        if std::env::var(SKIP_ENV).is_ok_and(|v| names_this(&v)) {
+            return Some(format!("listed in {}", SKIP_ENV));
+        }
+        let listed = Repository::discover(".")
+            .and_then(|r| r.config())
+            .and_then(|config| {
+                let mut listed = false;
+                config.multivar(SKIP_GIT_CONFIG, None)?.for_each(|entry| {
+                    listed |= entry.value().is_some_and(names_this);
+                })?;
+                Ok(listed)
+            })
+            .unwrap_or(false);
+        listed.then(|| format!("listed in git config {}", SKIP_GIT_CONFIG))
+    }
+
+    /// Run the hook command. When `stdin` is given it is fed to the command,
+    /// otherwise the command inherits our stdin.
+    ///
+    /// A hook that already passed on the same content with the same config, type,
+    /// arguments and stdin is skipped, unless `use_cache` is false.
+    pub fn run(
+        &self,
+        hook_args: &[String],
+        stdin: Option<&[u8]>,
+        options: &RunOptions,
+        sql_config: &SqlLiteConfig,
+    ) -> Result<RunOutcome, Box<dyn std::error::Error>> {
+        let hook_type = options.hook_type.as_ref();
+        if let Some(reason) = self.requested_skip() {
+            println!("Skipping {}, {}", self.name, reason);
+            sql_config.add_skipped_run(self.key())?;
+            history::save_skip(self, hook_type, sql_config)?;
+            return Ok(RunOutcome::Skipped(reason));
+        }
         if !self.check_files_match_glob() {
             println!("Pattern does not match the glob provided, skipping this!");
-            return Ok(());
+            return Ok(RunOutcome::NoMatch);
+        }
+
+        // Keyed before running, hooks like formatters may change what they look at
+        let cache_key = cache::key(self, hook_type, hook_args, stdin).ok();
+        if let Some(key) = cache_key.as_ref().filter(|_| options.use_cache) {
+            if sql_config.is_cached_pass(self.key(), key)? {
+                println!("Skipping {}, cached pass", self.name);
+                history::save_skip(self, hook_type, sql_config)?;
+                return Ok(RunOutcome::Cached);
+            }
         }
 
-        println!("Running {}", self.command.cmd);
+        // A flaky hook gets its retries, any other failure is real
+        let retries = match self.retries {
+            Some(n) if n > 0 && history::is_flaky(self, sql_config)? => i64::from(n),
+            _ => 0,
+        };
+        let mut attempt = 1;
+        loop {
+            println!("Running {}", self.command.cmd);
+            let mut record = history::start(self, hook_type);
+            record.attempt = attempt;
+            record.config_hash = cache_key.as_ref().map(|k| k.config_hash.clone());
+            record.content_key = cache_key.as_ref().map(|k| k.run_key());
+            let started = Instant::now();
+            let (status, captured) = self.execute(hook_args, stdin)?;
+            record.exit_code = status.and_then(|s| s.code()).map(i64::from);
+            record.signal = status.and_then(|s| s.signal()).map(i64::from);
+
+            if status.is_some_and(|s| s.success()) {
+                // exit code was zero
+                sql_config.add_successful_run(self.key())?;
+                history::save(&mut record, RunStatus::Pass, started, sql_config)?;
+                write_last_run(&record, &captured);
+                if let Some(key) = &cache_key {
+                    sql_config.add_cached_pass(self.key(), key, time::now())?;
+                }
+                return Ok(RunOutcome::Passed);
+            }
+
+            // non‐zero, signal‐terminated or timed out
+            sql_config.add_failed_run(self.key())?;
+            let outcome = match status {
+                Some(_) => RunStatus::Fail,
+                None => RunStatus::Timeout,
+            };
+            let run_id = history::save(&mut record, outcome, started, sql_config)?;
+            logs::save(run_id, &captured, sql_config)?;
+            write_last_run(&record, &captured);
+            if attempt <= retries {
+                attempt += 1;
+                println!(
+                    "Retrying {}, it is flaky (attempt {} of {})",
+                    self.name,
+                    attempt,
+                    retries + 1
+                );
+                continue;
+            }
+            return match status.map(|s| s.code()) {
+                // exited with some code != 0
+                Some(Some(code)) => Err(format!("Command failed with status {}", code).into()),
+                // e.g. killed by signal on Unix
+                Some(None) => Err("Cmd terminated by signal".into()),
+                None => Err(format!(
+                    "{} timed out after {}s",
+                    self.name,
+                    self.timeout.unwrap_or_default()
+                )
+                .into()),
+            };
+        }
+    }
+
+    /// Run the command once, showing and capturing its output. The status is
+    /// `None` when it was killed for running past the timeout.
+    fn execute(
+        &self,
+        hook_args: &[String],
+        stdin: Option<&[u8]>,
+    ) -> Result<(Option<ExitStatus>, Captured), Box<dyn std::error::Error>> {
         let mut cmd = Command::new(&self.command.cmd);
         if let Some(v) = &self.command.args {
             let args = v.split(" ");
             cmd.args(args);
         };
+        if self.pass_args {
+            cmd.args(hook_args);
+        }
         if let Some(v) = &self.command.directory {
             cmd.current_dir(v);
         };
-        let status = cmd
+        if stdin.is_some() {
+            cmd.stdin(Stdio::piped());
+        }
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = cmd
             .spawn()
-            .unwrap_or_else(|_| panic!("Failed to execute {:?}", self.command.cmd))
-            .wait()?;
-        if status.success() {
-            // exit code was zero
-            sql_config.add_successful_run(&self.name)?;
-            Ok(())
-        } else {
-            // non‐zero or signal‐terminated
-            sql_config.add_failed_run(&self.name)?;
-            match status.code() {
-                // exited with some code != 0
-                Some(code) => Err(format!("Command failed with status {}", code).into()),
-                // e.g. killed by signal on Unix
-                None => Err("Cmd terminated by signal".into()),
+            .unwrap_or_else(|_| panic!("Failed to execute {:?}", self.command.cmd));
+        let tee = Tee::start(&mut child);
+        // Input larger than the pipe buffer blocks until the command reads it,
+        // so it is written from its own thread and the timeout still applies
+        let writer = match (stdin, child.stdin.take()) {
+            (Some(input), Some(mut child_stdin)) => {
+                let input = input.to_vec();
+                Some(std::thread::spawn(move || child_stdin.write_all(&input)))
+            }
+            _ => None,
+        };
+        let status = match self.timeout {
+            Some(secs) => wait_with_timeout(&mut child, Duration::from_secs(secs))?,
+            None => Some(child.wait()?),
+        };
+        // The command may exit without reading its input. After a timeout the
+        // writer is left to fail on the closed pipe by itself
+        if let (Some(_), Some(writer)) = (status, writer) {
+            match writer
+                .join()
+                .map_err(|_| "Failed to write the hook input")?
+            {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+                _ => (),
             }
         }
+        let captured = match status {
+            Some(_) => tee.finish(),
+            None => tee.snapshot(),
+        };
+        Ok((status, captured))
     }
 
     pub fn apply_hook(
         &self,
+        repo: &Path,
         hook_type: &HookTypes,
+        options: &ApplyOptions,
         sql_config: &SqlLiteConfig,
     ) -> Result<(), Box<dyn std::error::Error>> {
         println!("Apply hook {} as {}", self.name, hook_type);
-        let cd = std::env::current_dir()?
+        // First check if the repo is a git repo
+        fs::read_dir(repo.join(".git"))?;
+
+        // Rows are keyed by the canonical path, whichever way the repo was reached
+        let repo = &fs::canonicalize(repo)?;
+        let cd = repo
             .to_str()
-            .expect("Failed to get current dir")
+            .expect("Repo path is not valid UTF-8")
             .to_string();
 
-        // First check if the current directory is a git repo
-        fs::read_dir("./.git/")?;
-
-        // Check if there is already a git hook
-        let mut already_managed = false;
+        if sql_config.check_if_hook_is_same(&cd, hook_type, self.key())? {
+            return Err("Git hooks is already setup for this repo with this type, aborting".into());
+        }
 
-        let file_path = format!("./.git/hooks/{}", hook_type);
-        match sql_config.check_if_hook_is_known(&cd, hook_type) {
-            Ok(true) => match sql_config.check_if_hook_is_same(&cd, hook_type, &self.name) {
-                Ok(false) => {
-                    println!("There is already a existing managed git hook, will try to truncate exisiting config");
-                    already_managed = true;
-                }
-                Ok(true) => {
-                    return Err(
-                        "Git hooks is already setup for this repo with this type, aborting".into(),
-                    );
-                }
-                Err(e) => {
+        // Check if there is already a git hook that we do not manage
+        let file_path = shim::hook_path(repo, hook_type);
+        let legacy_path = shim::legacy_hook_path(repo, hook_type);
+        let managed = sql_config.check_if_hook_is_known(&cd, hook_type)?
+            || fs::read_to_string(&file_path).is_ok_and(|c| shim::is_shim(&c));
+        if !managed && fs::metadata(&file_path).is_ok() {
+            if options.chain {
+                if fs::metadata(&legacy_path).is_ok() {
                     return Err(format!(
-                        "Failed to check for pre exisiting similar hooks, error: {}",
-                        e
+                        "Cannot chain the existing hook, {} already exists",
+                        legacy_path.display()
                     )
                     .into());
                 }
-            },
-            Ok(false) => {
-                if fs::read(&file_path).is_ok() {
-                    return Err(
-                        "Failed to apply hook, the selected hook type already exists, and may not be managed".into(),
-                    );
-                }
-            }
-            Err(e) => {
+                println!("Moving existing hook to {}", legacy_path.display());
+                fs::rename(&file_path, &legacy_path)?;
+            } else if options.force {
+                let backup_path =
+                    backup::backup_hook(&file_path, &cd, hook_type, &options.data_dir, sql_config)?;
+                println!(
+                    "Overwriting existing hook, backup saved to {:?}",
+                    backup_path
+                );
+                fs::remove_file(&file_path)?;
+            } else {
                 return Err(
-                    format!("Failed to check for pre exisiting hooks, error: {}", e).into(),
+                    "Failed to apply hook, the selected hook type already exists, and may not be managed (use --chain to keep it, or --force to overwrite it)".into(),
                 );
             }
         }
 
-        let exe_location = std::env::current_exe()?;
-        let file_content = format!("{} run {}", exe_location.to_str().expect(""), self.name);
-        if !already_managed {
-            let mut hook_file = fs::File::create(&file_path)?;
-            writeln!(hook_file, "#!/usr/bin/env sh")?;
-            writeln!(hook_file, "set -e")?;
-            writeln!(hook_file, "{}", file_content)?;
-            drop(hook_file);
-
-            let mut permissions = fs::metadata(&file_path)?.permissions();
-            permissions.set_mode(0o755);
-            set_permissions(file_path, permissions)?;
-        } else {
-            let mut hook_file = OpenOptions::new().append(true).open(file_path)?;
-            writeln!(hook_file, "{}", file_content)?;
-            drop(hook_file);
-        }
+        sql_config.add_hook(self.key())?;
+        sql_config.add_hook_to_repo(self.key(), &cd, hook_type)?;
+        identity::remember(repo, sql_config)?;
 
-        sql_config.add_hook(&self.name)?;
-        sql_config.add_hook_to_repo(&self.name, &cd, hook_type)?;
+        // (Re)write the shim, this also upgrades scripts from older versions
+        shim::write_shim(repo, hook_type, &options.shim, sql_config)?;
 
         Ok(())
     }
 
     pub fn remove_hook(
-        self,
+        &self,
+        repo: &Path,
         hook_type: &HookTypes,
+        shim: &ShimOptions,
         sql_config: &SqlLiteConfig,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // find current directory
-        // Find the current directory and hooktype match in sql config
-        // Remove that entry from the hook file and sql
-        let cd = std::env::current_dir()?;
-        let cd_str = cd.into_os_string().into_string().unwrap();
-        match sql_config.check_if_hook_is_same(cd_str.as_str(), hook_type, self.name.as_str()) {
-            Ok(true) => (),
-            _ => return Err("Trying to remove unknown hook, aborting!".into()),
-        }
-
-        // Remove the execution from the hook
-        let file_path = format!("./.git/hooks/{}", hook_type);
-        let file = fs::File::open(&file_path)?;
-        let reader = BufReader::new(file);
-
-        let lines: Vec<String> = reader
-            .lines()
-            .map_while(Result::ok)
-            .filter(|line| -> bool { !line.contains(self.name.as_str()) })
-            .collect();
-
-        let only_shebang = lines.len() == 1 && lines[0].starts_with("#!");
-        let is_empty = lines.is_empty();
-
-        if is_empty || only_shebang {
-            fs::remove_file(file_path)?;
-        } else {
-            let mut file = fs::File::create(&file_path)?;
-            for line in lines {
-                writeln!(file, "{}", line)?;
-            }
-        }
-
-        // Remove the hook from sqllite
-        sql_config.remove_hook(cd_str.as_str(), hook_type, self.name.as_str())
+        uninstall_hook(self.key(), repo, hook_type, shim, sql_config)
     }
 
     pub fn delete_hook(
@@ -283,16 +508,162 @@ impl GitHook {
         config_file: PathBuf,
     ) -> Result<(), Box<dyn std::error::Error>> {
         // First check the hook is not used by any
-        if matches!(sql_config.check_if_hook_is_used(&self.name), Ok(true)) {
+        if matches!(sql_config.check_if_hook_is_used(self.key()), Ok(true)) {
             return Err("The hook is in use; please remove those first.".into());
         }
         // Then remove from config.yml
-        let f = std::fs::File::open(&config_file)?;
-        let mut hooks: Vec<GitHook> = serde_yaml::from_reader(f)?;
-        hooks.retain(|h| h.name != self.name);
-        let yaml_str = serde_yaml::to_string(&hooks)?;
-        fs::write(&config_file, yaml_str)?;
+        let mut config = yml_parser::read_config(&config_file)?;
+        config.hooks.retain(|h| h.name != self.name);
+        yml_parser::write_config(&config_file, &config)
+    }
+}
 
-        Ok(())
+/// Rename a hook in the config, in the profiles and repo rules referring to it, in
+/// the database, and in every installed hook script.
+///
+/// Hooks with an `id` keep their database rows, only the scripts are rewritten.
+pub fn rename_hook(
+    config_file: &Path,
+    old: &str,
+    new: &str,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = yml_parser::read_config(config_file)?;
+    if new.trim().is_empty() {
+        return Err("The new name must not be empty".into());
+    }
+    if config.find_hook(new).is_some() || config.find_hook_by_key(new).is_some() {
+        return Err(format!("A hook named {} already exists in the config", new).into());
+    }
+    let hook = config
+        .hooks
+        .iter_mut()
+        .find(|h| h.name == old)
+        .ok_or_else(|| format!("No hook named {} in the config", old))?;
+    let old_key = hook.key().to_string();
+    hook.name = new.to_string();
+    let new_key = hook.key().to_string();
+
+    let hook_sets = config
+        .profiles
+        .values_mut()
+        .chain(config.repos.iter_mut().map(|r| &mut r.hooks));
+    for names in hook_sets.flat_map(|set| set.values_mut()) {
+        for name in names.iter_mut().filter(|n| *n == old) {
+            *name = new.to_string();
+        }
+    }
+
+    // The config is written last, so a failure leaves the database untouched
+    sql_config.transaction(|| {
+        if old_key != new_key {
+            sql_config.rename_hook(&old_key, &new_key)?;
+        }
+        yml_parser::write_config(config_file, &config)
+    })?;
+
+    let shim = ShimOptions {
+        template: config.load_shim_template()?,
+        config_file: Some(std::path::absolute(config_file)?),
+    };
+    let rewritten = rewrite_scripts(&new_key, &shim, sql_config)?;
+    println!(
+        "Renamed {} to {}, rewrote {} hook script(s)",
+        old, new, rewritten
+    );
+    Ok(())
+}
+
+/// Rewrite the hook scripts of every repo `key` is installed in, returns how many
+fn rewrite_scripts(
+    key: &str,
+    shim: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut rewritten = 0;
+    for row in sql_config.list_all_repo_hooks()? {
+        let repo = Path::new(&row.repo);
+        if row.name != key || !repo.join(".git").is_dir() {
+            continue;
+        }
+        let Ok(hook_type) = HookTypes::from_str(&row.hook_type) else {
+            continue;
+        };
+        shim::write_shim(repo, &hook_type, shim, sql_config)?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+/// Hooks that were given an `id` after they were installed, so their database
+/// rows are still under their name and nothing under the id yet
+pub fn added_ids<'a>(
+    config: &'a Config,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<&'a GitHook>, Box<dyn std::error::Error>> {
+    let mut hooks = Vec::new();
+    for hook in &config.hooks {
+        if hook.key() != hook.name
+            && sql_config.is_hook_managed(&hook.name)?
+            && !sql_config.is_hook_managed(hook.key())?
+        {
+            hooks.push(hook);
+        }
+    }
+    Ok(hooks)
+}
+
+/// Move the database rows of a hook from its name to the `id` it was given,
+/// so its installs, stats and cached results are kept
+pub fn adopt_id(
+    name: &str,
+    id: &str,
+    shim: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sql_config.transaction(|| sql_config.rename_hook(name, id))?;
+    rewrite_scripts(id, shim, sql_config)?;
+    Ok(())
+}
+
+/// Remove an installed hook from a repo, by name only, so it also works for
+/// hooks that are gone from the config
+pub fn uninstall_hook(
+    name: &str,
+    repo: &Path,
+    hook_type: &HookTypes,
+    shim: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cd_str = repo.to_str().expect("Repo path is not valid UTF-8");
+    match sql_config.check_if_hook_is_same(cd_str, hook_type, name) {
+        Ok(true) => (),
+        _ => return Err("Trying to remove unknown hook, aborting!".into()),
+    }
+
+    // The shim reads the installed hooks from sqllite, so this is enough
+    // as long as other hooks remain
+    sql_config.remove_hook(cd_str, hook_type, name)?;
+
+    let file_path = shim::hook_path(repo, hook_type);
+    let legacy_path = shim::legacy_hook_path(repo, hook_type);
+
+    if sql_config.check_if_hook_is_known(cd_str, hook_type)? {
+        shim::write_shim(repo, hook_type, shim, sql_config)?;
+    } else if fs::metadata(&legacy_path).is_ok() {
+        // Put the chained hook back in place
+        println!("Restoring previous hook from {}", legacy_path.display());
+        fs::rename(&legacy_path, &file_path)?;
+    } else {
+        if fs::metadata(&file_path).is_ok() {
+            fs::remove_file(&file_path)?;
+        }
+        if let Some(backup) = sql_config.list_backups(cd_str, hook_type)?.first() {
+            println!(
+                "The original {} hook was backed up, run `crab-hooks restore {} --id {}` to put it back",
+                hook_type, hook_type, backup.id
+            );
+        }
     }
+    Ok(())
 }
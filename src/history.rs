@@ -0,0 +1,161 @@
+use git2::Repository;
+use std::{path::Path, time::Instant};
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    git_hook::GitHook,
+    hook_types::HookTypes,
+    sqllite::{RunFilter, RunRecord, SqlLiteConfig},
+    time,
+    yml_parser::{self, HistoryRetention},
+};
+
+/// How a recorded run ended
+#[derive(Display, Clone, EnumString, Debug, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum RunStatus {
+    Pass,
+    Fail,
+    /// Skipped on request, because the hook is disabled, or by the result cache
+    Skip,
+    /// Killed after running longer than the hook's `timeout`
+    Timeout,
+}
+
+/// A run of `hook` in the repo of the current directory, starting now.
+/// The outcome is filled in by `save`.
+pub fn start(hook: &GitHook, hook_type: Option<&HookTypes>) -> RunRecord {
+    let repo = Repository::discover(".").ok();
+    let repo_path = repo
+        .as_ref()
+        .and_then(|r| r.workdir())
+        .and_then(|p| p.canonicalize().ok())
+        .map(|p| p.to_string_lossy().to_string());
+    let head = repo
+        .as_ref()
+        .and_then(|r| r.head().ok())
+        .and_then(|h| h.target())
+        .map(|oid| oid.to_string());
+    RunRecord {
+        id: 0,
+        hook: hook.key().to_string(),
+        repo: repo_path,
+        hook_type: hook_type.map(|t| t.to_string()),
+        head,
+        started_at: time::now(),
+        duration_ms: 0,
+        exit_code: None,
+        signal: None,
+        outcome: String::new(),
+        config_hash: None,
+        content_key: None,
+        attempt: 1,
+    }
+}
+
+/// Record how a run started at `started` ended, filling in its outcome and id
+pub fn save(
+    record: &mut RunRecord,
+    status: RunStatus,
+    started: Instant,
+    sql_config: &SqlLiteConfig,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    record.outcome = status.to_string();
+    record.duration_ms = started.elapsed().as_millis() as i64;
+    record.id = sql_config.add_run(record)?;
+    Ok(record.id)
+}
+
+/// Record a hook that did not run
+pub fn save_skip(
+    hook: &GitHook,
+    hook_type: Option<&HookTypes>,
+    sql_config: &SqlLiteConfig,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    save(
+        &mut start(hook, hook_type),
+        RunStatus::Skip,
+        Instant::now(),
+        sql_config,
+    )
+}
+
+/// How far back `is_flaky` looks, in seconds
+pub const FLAKY_WINDOW: i64 = 30 * 86400;
+
+/// Whether a hook recently passed and failed on the same input, so a failure may
+/// be worth a retry
+pub fn is_flaky(
+    hook: &GitHook,
+    sql_config: &SqlLiteConfig,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    sql_config.has_mixed_outcomes(hook.key(), time::now() - FLAKY_WINDOW)
+}
+
+/// Delete the runs that fall outside the retention limits
+pub fn apply_retention(
+    retention: &HistoryRetention,
+    sql_config: &SqlLiteConfig,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let before = time::now() - retention.keep_days * 86400;
+    sql_config.trim_runs(before, retention.max_runs)
+}
+
+/// Apply the retention limits of the config after hooks ran. Best effort, a broken
+/// config or database only prints a warning.
+pub fn trim(config_file: &Path, sql_config: &SqlLiteConfig) {
+    let retention = yml_parser::read_config(config_file)
+        .ok()
+        .and_then(|c| c.history)
+        .unwrap_or_default();
+    if let Err(e) = apply_retention(&retention, sql_config) {
+        eprintln!("crab-hooks: could not trim the run history: {}", e);
+    }
+}
+
+/// Format a duration in milliseconds, like `850ms`, `12.3s` or `4m05s`
+pub fn format_duration(ms: i64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        let secs = ms / 1000;
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Print the runs matching `filter`, newest first
+pub fn print_history(
+    filter: &RunFilter,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let runs = sql_config.list_runs(filter)?;
+    if runs.is_empty() {
+        println!("No runs recorded");
+        return Ok(());
+    }
+    for run in &runs {
+        let exit = match (run.exit_code, run.signal) {
+            (Some(code), _) => format!("exit {}", code),
+            (None, Some(signal)) => format!("signal {}", signal),
+            (None, None) => "-".to_string(),
+        };
+        let exit = match run.attempt {
+            1 => exit,
+            attempt => format!("{}, attempt {}", exit, attempt),
+        };
+        println!(
+            "{}  {:<7} {}  {}  {}  {}  {}  {}",
+            time::format_time(run.started_at),
+            run.outcome,
+            run.hook,
+            run.hook_type.as_deref().unwrap_or("run"),
+            format_duration(run.duration_ms),
+            exit,
+            run.repo.as_deref().unwrap_or("-"),
+            run.head.as_deref().map_or("-", |h| &h[..h.len().min(8)]),
+        );
+    }
+    Ok(())
+}
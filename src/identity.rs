@@ -0,0 +1,132 @@
+use git2::Repository;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    hook_types::HookTypes,
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+};
+
+/// What identifies a repository independently of where it is checked out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoIdentity {
+    /// First commit of HEAD's first-parent line, `None` before the first commit
+    pub root_commit: Option<String>,
+    /// URL of `origin`, or of the first remote when there is no `origin`
+    pub remote: Option<String>,
+}
+
+/// The current directory with symlinks resolved, the key used in the database
+pub fn current_repo() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(fs::canonicalize(std::env::current_dir()?)?)
+}
+
+/// Read the identity of a repository
+pub fn identify(repo: &Path) -> Result<RepoIdentity, Box<dyn std::error::Error>> {
+    let git_repo = Repository::open(repo)?;
+
+    // Following first parents only avoids sorting the whole history
+    let mut root_commit = None;
+    if let Ok(mut walk) = git_repo.revwalk() {
+        walk.simplify_first_parent()?;
+        if walk.push_head().is_ok() {
+            root_commit = walk.flatten().last().map(|oid| oid.to_string());
+        }
+    }
+
+    let remotes = git_repo.remotes()?;
+    let name = if remotes.iter().flatten().any(|r| r == "origin") {
+        Some("origin")
+    } else {
+        remotes.iter().flatten().next()
+    };
+    let remote = name
+        .and_then(|n| git_repo.find_remote(n).ok())
+        .and_then(|r| r.url().map(str::to_string));
+
+    Ok(RepoIdentity {
+        root_commit,
+        remote,
+    })
+}
+
+/// Record the identity of a repo, so it can be found again after it moves
+pub fn remember(repo: &Path, sql_config: &SqlLiteConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let identity = identify(repo)?;
+    sql_config.set_repo_identity(&repo.to_string_lossy(), &identity)
+}
+
+/// The known repo that `repo` was moved from: one with the same identity whose
+/// recorded path no longer exists. Repos without commits cannot be matched.
+pub fn find_previous_location(
+    repo: &Path,
+    sql_config: &SqlLiteConfig,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let identity = identify(repo)?;
+    if identity.root_commit.is_none() {
+        return Ok(None);
+    }
+    let repo_str = repo.to_string_lossy();
+    let candidates: Vec<String> = sql_config
+        .list_repo_identities()?
+        .into_iter()
+        .filter(|(path, id)| {
+            *path != repo_str && *id == identity && !Path::new(path).join(".git").is_dir()
+        })
+        .map(|(path, _)| path)
+        .collect();
+    match candidates.as_slice() {
+        [] => Ok(None),
+        [previous] => Ok(Some(PathBuf::from(previous))),
+        _ => Err(format!(
+            "{} matches several moved repos ({}), pick one with --from",
+            repo.display(),
+            candidates.join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Move every row of `from` over to `to` and rewrite the hook scripts there
+pub fn relocate(
+    from: &Path,
+    to: &Path,
+    shim: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !to.join(".git").is_dir() {
+        return Err(format!("{} is not a git repository", to.display()).into());
+    }
+    let (from_str, to_str) = (from.to_string_lossy(), to.to_string_lossy());
+    sql_config.transaction(|| sql_config.relocate_repo(&from_str, &to_str))?;
+    remember(to, sql_config)?;
+
+    for (repo, hook_type) in sql_config.list_installs()? {
+        if repo != to_str {
+            continue;
+        }
+        let Ok(hook_type) = HookTypes::from_str(&hook_type) else {
+            continue;
+        };
+        shim::write_shim(to, &hook_type, shim, sql_config)?;
+    }
+    println!("Relocated {} to {}", from.display(), to.display());
+    Ok(())
+}
+
+/// Relocate `repo` if it was moved from a known location, returns where it came from
+pub fn auto_relocate(
+    repo: &Path,
+    shim: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let Some(previous) = find_previous_location(repo, sql_config)? else {
+        return Ok(None);
+    };
+    relocate(&previous, repo, shim, sql_config)?;
+    Ok(Some(previous))
+}
@@ -1,4 +1,23 @@
+pub mod adopt;
+pub mod backup;
+pub mod bulk;
+pub mod cache;
+pub mod disable;
+pub mod dispatch;
+pub mod doctor;
 pub mod git_hook;
+pub mod history;
 pub mod hook_types;
+pub mod identity;
+pub mod logs;
+pub mod notes;
+pub mod profile;
+pub mod prune;
+pub mod repos;
+pub mod scan;
+pub mod shim;
 pub mod sqllite;
+pub mod stats;
+pub mod sync;
+pub mod time;
 pub mod yml_parser;
@@ -0,0 +1,203 @@
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use git2::Repository;
+use std::{
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    path::PathBuf,
+    process::Child,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use crate::{
+    sqllite::{RunRecord, SqlLiteConfig},
+    time,
+};
+
+/// Most bytes of output kept per run, the oldest output is dropped first
+pub const LOG_LIMIT: usize = 256 * 1024;
+
+/// The tail of what a command printed, stdout and stderr interleaved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Captured {
+    pub output: Vec<u8>,
+    /// Output was dropped to stay within `LOG_LIMIT`
+    pub truncated: bool,
+}
+
+impl Captured {
+    /// Append output, dropping the oldest beyond `LOG_LIMIT`
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+        if self.output.len() > LOG_LIMIT {
+            let excess = self.output.len() - LOG_LIMIT;
+            self.output.drain(..excess);
+            self.truncated = true;
+        }
+    }
+}
+
+/// Copies the output of a command to ours as it comes, keeping the tail of it
+pub struct Tee {
+    captured: Arc<Mutex<Captured>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+fn copy(
+    mut from: impl Read + Send + 'static,
+    mut to: impl Write + Send + 'static,
+    captured: Arc<Mutex<Captured>>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut buffer = [0; 8192];
+        while let Ok(n) = from.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let _ = to.write_all(&buffer[..n]);
+            let _ = to.flush();
+            if let Ok(mut captured) = captured.lock() {
+                captured.push(&buffer[..n]);
+            }
+        }
+    })
+}
+
+impl Tee {
+    /// Start copying, the child needs piped stdout and stderr
+    pub fn start(child: &mut Child) -> Tee {
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(copy(stdout, std::io::stdout(), captured.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(copy(stderr, std::io::stderr(), captured.clone()));
+        }
+        Tee { captured, readers }
+    }
+
+    /// Wait until the command closed its output and return what it printed
+    pub fn finish(mut self) -> Captured {
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+        self.snapshot()
+    }
+
+    /// What was printed so far. For a killed command, whose children may still
+    /// hold its output open.
+    pub fn snapshot(&self) -> Captured {
+        self.captured.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+}
+
+pub fn compress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+pub fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    ZlibDecoder::new(bytes).read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Keep the output of a failed run
+pub fn save(
+    run_id: i64,
+    captured: &Captured,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    sql_config.add_run_log(
+        run_id,
+        &compress(&captured.output)?,
+        captured.output.len() as i64,
+        captured.truncated,
+    )
+}
+
+/// `.git/crab-hooks/last-run.log` of the repo in the current directory
+pub fn last_run_path() -> Option<PathBuf> {
+    let repo = Repository::discover(".").ok()?;
+    Some(repo.path().join("crab-hooks").join("last-run.log"))
+}
+
+/// Start a fresh `last-run.log` for the hooks of one git command
+pub fn start_last_run(title: &str) -> std::io::Result<()> {
+    let Some(path) = last_run_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(
+        path,
+        format!("crab-hooks {}, {}\n", title, time::format_time(time::now())),
+    )
+}
+
+/// Add the output of a run to `last-run.log`
+pub fn append_last_run(run: &RunRecord, captured: &Captured) -> std::io::Result<()> {
+    let Some(path) = last_run_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "\n{}", header(run))?;
+    file.write_all(&captured.output)?;
+    Ok(())
+}
+
+fn header(run: &RunRecord) -> String {
+    let exit = match (run.exit_code, run.signal) {
+        (Some(code), _) => format!(", exit {}", code),
+        (None, Some(signal)) => format!(", signal {}", signal),
+        (None, None) => String::new(),
+    };
+    let attempt = match run.attempt {
+        1 => String::new(),
+        attempt => format!(", attempt {}", attempt),
+    };
+    format!(
+        "== {} {} {}: {}{}{} ==",
+        time::format_time(run.started_at),
+        run.hook,
+        run.hook_type.as_deref().unwrap_or("run"),
+        run.outcome,
+        exit,
+        attempt
+    )
+}
+
+/// Print the output of the newest `last` failed runs, of one hook or all of them
+pub fn print_logs(
+    hook: Option<&str>,
+    last: i64,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let logs = sql_config.list_run_logs(hook, last)?;
+    if logs.is_empty() {
+        println!("No failed runs with output recorded");
+        return Ok(());
+    }
+    // Oldest first, so the newest ends up closest to the prompt
+    for log in logs.iter().rev() {
+        println!("{}", header(&log.run));
+        if let Some(repo) = &log.run.repo {
+            println!("repo: {}", repo);
+        }
+        if log.truncated {
+            println!("[output truncated to the last {} bytes]", LOG_LIMIT);
+        }
+        let output = decompress(&log.output)?;
+        print!("{}", String::from_utf8_lossy(&output));
+        if !output.ends_with(b"\n") {
+            println!();
+        }
+    }
+    Ok(())
+}
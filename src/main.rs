@@ -1,11 +1,17 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use crab_hooks::{
-    git_hook::GitHook,
+    adopt, backup, bulk, cache, disable, dispatch, doctor,
+    git_hook::{self, ApplyOptions, GitHook, RunOptions},
+    history::{self, RunStatus},
     hook_types::HookTypes,
-    sqllite,
-    yml_parser::{self, test_config},
+    identity, logs, notes, profile, prune, repos, scan,
+    shim::{self, ShimOptions},
+    sqllite::{self, RunFilter},
+    stats::{self, OutputFormat},
+    sync, time,
+    yml_parser::{self, test_config, ConfigError, CONFIG_ERROR_EXIT_CODE},
 };
 
 #[derive(Parser)]
@@ -19,22 +25,87 @@ struct Cli {
     #[arg(long, global = true)]
     force: bool,
 
+    /// Skip validating the config before apply-hook, run and delete-hook
     #[arg(long = "no-test", global = true)]
     no_test: bool,
 
+    /// Run hooks even when they already passed on the same content
+    #[arg(long = "no-cache", global = true)]
+    no_cache: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Which repositories apply-hook and remove-hook act on, the current one by default
+#[derive(Args)]
+struct RepoSelection {
+    /// Act on this repository, may be repeated
+    #[arg(long = "repo")]
+    repos: Vec<PathBuf>,
+    /// Read repository paths from a file, one per line
+    #[arg(long)]
+    repos_from: Option<PathBuf>,
+    /// Act on every repository registered by scan or with hooks installed
+    #[arg(long)]
+    all_known: bool,
+    /// Keep going when a repository fails
+    #[arg(long)]
+    continue_on_error: bool,
+}
+
+impl RepoSelection {
+    fn is_bulk(&self) -> bool {
+        !self.repos.is_empty() || self.repos_from.is_some() || self.all_known
+    }
+
+    /// Run `f` on the current repo, or on every selected repo with a report
+    fn for_each<F>(
+        &self,
+        sql_config: &sqllite::SqlLiteConfig,
+        mut f: F,
+    ) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnMut(&Path) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        if !self.is_bulk() {
+            return f(&identity::current_repo()?);
+        }
+        let repos = bulk::resolve_repos(
+            &self.repos,
+            self.repos_from.as_deref(),
+            self.all_known,
+            sql_config,
+        )?;
+        bulk::for_each_repo(&repos, self.continue_on_error, f)
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    ///// Scan for Git repositories
-    // Scan {
-    //     #[arg(default_value = "~")]
-    //     dir: String,
-    // },
-    /// List all managed repositories
-    ListRepos,
+    /// Scan for Git repositories
+    Scan {
+        #[arg(default_value = "~")]
+        dir: String,
+        /// How many directory levels to descend
+        #[arg(long, default_value_t = 5)]
+        max_depth: usize,
+        /// Record the repositories found for later bulk operations
+        #[arg(long)]
+        register: bool,
+    },
+    /// List all managed repositories and the hooks installed in them
+    ListRepos {
+        /// Only show repos using this hook
+        #[arg(long)]
+        hook: Option<String>,
+        /// Only show this hook type
+        #[arg(long)]
+        hook_type: Option<HookTypes>,
+        /// Print the inventory as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// List hooks in a given repository
     ListHooks,
     /// Apply a hook to one or more repositories
@@ -42,21 +113,194 @@ enum Commands {
         hook_name: String,
         #[arg(required = true)]
         hook_type: HookTypes,
+        /// Keep an existing unmanaged hook and run it before crab-hooks
+        #[arg(long)]
+        chain: bool,
+        #[command(flatten)]
+        selection: RepoSelection,
     },
     RemoveHook {
         hook_name: String,
         #[arg(required = true)]
         hook_type: HookTypes,
+        #[command(flatten)]
+        selection: RepoSelection,
     },
     DeleteHook {
         #[arg(required = true)]
         hook_name: String,
     },
+    /// Switch off an installed hook in the current repo without uninstalling it
+    Disable {
+        hook_name: String,
+        /// Only this hook type, every type the hook is installed as by default
+        #[arg(long = "type")]
+        hook_type: Option<HookTypes>,
+        /// Turn it back on after a duration (`12h`, `1d`) or at a date (`2026-05-01`, UTC)
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Turn a disabled hook in the current repo back on
+    Enable {
+        hook_name: String,
+        /// Only this hook type, every type the hook is installed as by default
+        #[arg(long = "type")]
+        hook_type: Option<HookTypes>,
+    },
+    /// Rename a hook in the config, the database and every installed hook script
+    RenameHook { old: String, new: String },
+    /// Install every hook of a profile from the config
+    ApplyProfile {
+        profile: String,
+        /// Re-apply the profile to every repo already using it, in place of --repo,
+        /// --repos-from and --all-known
+        #[arg(long, conflicts_with_all = ["repos", "repos_from", "all_known"])]
+        update: bool,
+        #[command(flatten)]
+        selection: RepoSelection,
+    },
+    /// Bring every known repo in line with the `repos` rules of the config
+    Sync {
+        /// Only print the plan
+        #[arg(long)]
+        dry_run: bool,
+        /// Keep going when a repository fails
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+    /// Report where known repos drifted from the `repos` rules of the config
+    Status,
     /// Test if the config is valid
     Test,
+    /// Delete database rows about repos and hooks that no longer exist
+    Prune {
+        /// Only print what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check the config, the database and the hooks of the current repo
+    Doctor {
+        /// Repair the problems that can be repaired
+        #[arg(long)]
+        fix: bool,
+    },
 
     /// Run a hook in the current repo
-    Run { hook_name: String },
+    Run {
+        hook_name: String,
+        /// Arguments passed on to the hook command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// List the backups of a hook type in the current repo, or restore one
+    Restore {
+        #[arg(required = true)]
+        hook_type: HookTypes,
+        /// Id of the backup to restore
+        #[arg(long)]
+        id: Option<i64>,
+        /// Restore the newest backup
+        #[arg(long, conflicts_with = "id")]
+        latest: bool,
+    },
+
+    /// Re-associate a moved repository with the hooks recorded at its old path
+    Relocate {
+        /// Where the repository is now, the current directory by default
+        path: Option<PathBuf>,
+        /// Where it used to be, found from the repository's identity when omitted
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+
+    /// List past runs of the hooks, newest first
+    History {
+        /// Only runs of this hook
+        #[arg(long)]
+        hook: Option<String>,
+        /// Only runs in this repository, or the current one with `--here`
+        #[arg(long, conflicts_with = "here")]
+        repo: Option<PathBuf>,
+        /// Only runs in the current repository
+        #[arg(long)]
+        here: bool,
+        /// Only runs as this hook type
+        #[arg(long = "type")]
+        hook_type: Option<HookTypes>,
+        /// Only runs that ended like this: pass, fail, skip or timeout
+        #[arg(long)]
+        outcome: Option<RunStatus>,
+        /// Only runs since a duration ago (`12h`, `7d`) or a date (`2026-05-01`, UTC)
+        #[arg(long)]
+        since: Option<String>,
+        /// How many runs to show
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+
+    /// Print the output of failed runs, newest last
+    Logs {
+        /// Only runs of this hook
+        hook: Option<String>,
+        /// How many failed runs to show
+        #[arg(long, default_value_t = 1)]
+        last: i64,
+    },
+
+    /// Report success rates and durations of the hooks, and the slowest and most failing ones
+    Stats {
+        /// Only runs since a duration ago (`12h`, `7d`) or a date (`2026-05-01`, UTC)
+        #[arg(long, default_value = "30d")]
+        since: String,
+        /// How many hooks to list as slowest and most failing
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+        /// table, json or csv
+        #[arg(long, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+
+    /// Manage the cache of passing hook results
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Check that commits carry git notes showing the required hooks passed
+    Verify {
+        /// Commits to check, `A..B` or a single revision for its whole history
+        #[arg(default_value = "@{u}..HEAD")]
+        range: String,
+        /// Hook that must have passed, may be repeated; defaults to the
+        /// pre-commit and pre-push hooks installed in the current repo
+        #[arg(long)]
+        require: Vec<String>,
+    },
+
+    /// Run every hook installed as the given type in the current repo, used by the hook scripts
+    Dispatch {
+        hook_type: HookTypes,
+        /// Arguments git passed to the hook
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Rewrite every managed hook script to point at the current binary
+    Repair,
+
+    /// Move the existing hook scripts of the current repo into the config
+    Adopt {
+        /// Glob pattern(s) given to the adopted hooks
+        #[arg(long, default_value = "**")]
+        glob_pattern: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Forget every cached result
+    Clear,
 }
 
 fn find_hook(config_file: PathBuf, name: &String) -> Result<GitHook, Box<dyn std::error::Error>> {
@@ -69,42 +313,193 @@ fn find_hook(config_file: PathBuf, name: &String) -> Result<GitHook, Box<dyn std
     Err("No such hook found, please add it to the config".into())
 }
 
+/// The database knows hooks by key, which differs from the name when an id is set
+fn hook_key(config_file: &Path, name: &str) -> String {
+    match yml_parser::read_config(config_file) {
+        Ok(config) => config.key_of(name).to_string(),
+        Err(_) => name.to_string(),
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    match run(cli) {
+        Err(e) if e.is::<ConfigError>() => {
+            eprintln!("Error: {}", e);
+            std::process::exit(CONFIG_ERROR_EXIT_CODE);
+        }
+        result => result,
+    }
+}
 
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     let config_file = cli.config_file.unwrap_or_else(|| {
         let mut p = home::home_dir().expect("Could not find HOME directory");
         p.push(".config/crabs_hooks/config.yml");
         p
     });
-    let sql_config = if let Some(sql_db_path) = config_file.parent() {
-        let sql_db_path = sql_db_path.join("hooks.db");
-        sqllite::SqlLiteConfig::new(sql_db_path.to_str().unwrap())?
-    } else {
-        sqllite::SqlLiteConfig::new("mydb.db")?
+    let data_dir = config_file
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let sql_db_path = match config_file.parent() {
+        Some(dir) => dir.join("hooks.db"),
+        None => PathBuf::from("mydb.db"),
     };
 
+    // Doctor opens the database itself, so it can report when that fails
+    if let Commands::Doctor { fix } = &cli.command {
+        return doctor::doctor(&config_file, &sql_db_path, &data_dir, *fix);
+    }
+    let sql_config = sqllite::SqlLiteConfig::new(sql_db_path.to_str().unwrap())?;
+
+    // A hook given an id after it was installed keeps its rows under the new key
+    if let Ok(config) = yml_parser::read_config(&config_file) {
+        for hook in git_hook::added_ids(&config, &sql_config)? {
+            eprintln!(
+                "crab-hooks: {} was given the id {}, moving its installs and history over",
+                hook.name,
+                hook.key()
+            );
+            let shim = ShimOptions::load(&config_file)?;
+            git_hook::adopt_id(&hook.name, hook.key(), &shim, &sql_config)?;
+        }
+    }
+
+    let use_cache = !cli.no_cache && std::env::var_os(cache::NO_CACHE_ENV).is_none();
+
+    // Validate the hooks a mutating command touches, unless asked not to
+    if !cli.no_test {
+        match &cli.command {
+            Commands::ApplyHook { hook_name, .. }
+            | Commands::Run { hook_name, .. }
+            | Commands::DeleteHook { hook_name } => {
+                yml_parser::test_hooks(config_file.clone(), &[hook_name.as_str()])?;
+            }
+            Commands::Sync { .. } => {
+                yml_parser::test_config(config_file.clone())?;
+            }
+            Commands::ApplyProfile { profile, .. } => {
+                yml_parser::test_profile(config_file.clone(), profile)?;
+            }
+            // dispatch validates the hooks it will run, after the requested skips
+            _ => (),
+        }
+    }
+
     match &cli.command {
-        // Commands::Scan { dir } => println!("Scan"),
-        Commands::ListRepos => println!("List repos"),
+        Commands::Scan {
+            dir,
+            max_depth,
+            register,
+        } => {
+            return scan::scan(dir, *max_depth, *register, &sql_config);
+        }
+        Commands::ListRepos {
+            hook,
+            hook_type,
+            json,
+        } => {
+            let key = hook.as_deref().map(|name| hook_key(&config_file, name));
+            return repos::list_repos(key.as_deref(), hook_type.as_ref(), *json, &sql_config);
+        }
         Commands::ListHooks => {
             yml_parser::display_hooks(config_file);
         }
         Commands::ApplyHook {
             hook_name,
             hook_type,
+            chain,
+            selection,
         } => {
-            return find_hook(config_file, hook_name)
-                .expect("Failed to find the hook")
-                .apply_hook(hook_type, &sql_config);
+            let options = ApplyOptions {
+                chain: *chain,
+                force: cli.force,
+                data_dir: data_dir.clone(),
+                shim: ShimOptions::load(&config_file)?,
+            };
+            let hook = find_hook(config_file, hook_name).expect("Failed to find the hook");
+            return selection.for_each(&sql_config, |repo| {
+                hook.apply_hook(repo, hook_type, &options, &sql_config)
+            });
         }
         Commands::RemoveHook {
             hook_name,
             hook_type,
+            selection,
         } => {
-            return find_hook(config_file, hook_name)
-                .expect("Failed to find the hook")
-                .remove_hook(hook_type, &sql_config);
+            let shim = ShimOptions::load(&config_file)?;
+            let hook = find_hook(config_file, hook_name).expect("Failed to find the hook");
+            return selection.for_each(&sql_config, |repo| {
+                hook.remove_hook(repo, hook_type, &shim, &sql_config)
+            });
+        }
+        Commands::ApplyProfile {
+            profile: profile_name,
+            update,
+            selection,
+        } => {
+            let config = yml_parser::read_config(&config_file)?;
+            let options = ApplyOptions {
+                chain: false,
+                force: cli.force,
+                data_dir: data_dir.clone(),
+                shim: ShimOptions::load(&config_file)?,
+            };
+            let apply = |repo: &Path| {
+                profile::apply_profile(repo, profile_name, &config, &options, &sql_config)
+            };
+            if *update {
+                let repos: Vec<PathBuf> = sql_config
+                    .list_profile_repos(profile_name)?
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect();
+                return bulk::for_each_repo(&repos, selection.continue_on_error, apply);
+            }
+            return selection.for_each(&sql_config, apply);
+        }
+        Commands::Sync {
+            dry_run,
+            continue_on_error,
+        } => {
+            let config = yml_parser::read_config(&config_file)?;
+            let options = ApplyOptions {
+                chain: false,
+                force: cli.force,
+                data_dir: data_dir.clone(),
+                shim: ShimOptions::load(&config_file)?,
+            };
+            return sync::sync(&config, &options, *dry_run, *continue_on_error, &sql_config);
+        }
+        Commands::Status => {
+            let config = yml_parser::read_config(&config_file)?;
+            return sync::status(&config, &sql_config);
+        }
+        Commands::Disable {
+            hook_name,
+            hook_type,
+            until,
+        } => {
+            let now = time::now();
+            let until = until
+                .as_deref()
+                .map(|u| time::parse_until(u, now))
+                .transpose()?;
+            let key = hook_key(&config_file, hook_name);
+            let cd = identity::current_repo()?;
+            return disable::disable(&cd, &key, hook_type.as_ref(), until, &sql_config);
+        }
+        Commands::Enable {
+            hook_name,
+            hook_type,
+        } => {
+            let key = hook_key(&config_file, hook_name);
+            let cd = identity::current_repo()?;
+            return disable::enable(&cd, &key, hook_type.as_ref(), &sql_config);
+        }
+        Commands::RenameHook { old, new } => {
+            return git_hook::rename_hook(&config_file, old, new, &sql_config);
         }
         Commands::DeleteHook { hook_name } => {
             return find_hook(config_file.clone(), hook_name)
@@ -120,9 +515,125 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 return Err(e);
             }
         },
-        Commands::Run { hook_name } => {
-            let hook = find_hook(config_file, hook_name).expect("Failed to find hook");
-            return hook.run(&sql_config);
+        Commands::Run { hook_name, args } => {
+            let hook = find_hook(config_file.clone(), hook_name).expect("Failed to find hook");
+            let cd = identity::current_repo()?;
+            if disable::is_disabled(&cd.to_string_lossy(), hook.key(), None, &sql_config)? {
+                println!("Skipping {}, disabled in this repo", hook.name);
+                return Ok(());
+            }
+            if let Err(e) = logs::start_last_run(&format!("run {}", hook_name)) {
+                eprintln!("crab-hooks: could not write last-run.log: {}", e);
+            }
+            let options = RunOptions {
+                hook_type: None,
+                use_cache,
+            };
+            hook.run(args, None, &options, &sql_config)?;
+            history::trim(&config_file, &sql_config);
+        }
+        Commands::Dispatch { hook_type, args } => {
+            return dispatch::dispatch(
+                hook_type,
+                args,
+                config_file,
+                use_cache,
+                !cli.no_test,
+                &sql_config,
+            );
+        }
+        Commands::Restore {
+            hook_type,
+            id,
+            latest,
+        } => {
+            return backup::restore(hook_type, *id, *latest, &sql_config);
+        }
+        Commands::Repair => {
+            return shim::repair(&ShimOptions::load(&config_file)?, &sql_config);
+        }
+        Commands::Relocate { path, from } => {
+            let to = match path {
+                Some(path) => std::fs::canonicalize(path)?,
+                None => identity::current_repo()?,
+            };
+            let from = match from {
+                Some(from) => from.clone(),
+                None => identity::find_previous_location(&to, &sql_config)?.ok_or_else(|| {
+                    format!(
+                        "No moved repo matches {}, pass the old path with --from",
+                        to.display()
+                    )
+                })?,
+            };
+            let shim = ShimOptions::load(&config_file)?;
+            return identity::relocate(&from, &to, &shim, &sql_config);
+        }
+        Commands::History {
+            hook,
+            repo,
+            here,
+            hook_type,
+            outcome,
+            since,
+            limit,
+        } => {
+            let repo = match (repo, here) {
+                (Some(repo), _) => Some(std::fs::canonicalize(repo)?),
+                (None, true) => Some(identity::current_repo()?),
+                (None, false) => None,
+            };
+            let since = since
+                .as_deref()
+                .map(|s| time::parse_since(s, time::now()))
+                .transpose()?;
+            let filter = RunFilter {
+                hook: hook.as_deref().map(|name| hook_key(&config_file, name)),
+                repo: repo.map(|r| r.to_string_lossy().to_string()),
+                hook_type: hook_type.as_ref().map(|t| t.to_string()),
+                outcome: outcome.as_ref().map(|o| o.to_string()),
+                since,
+                limit: Some(*limit),
+            };
+            return history::print_history(&filter, &sql_config);
+        }
+        Commands::Logs { hook, last } => {
+            let key = hook.as_deref().map(|name| hook_key(&config_file, name));
+            return logs::print_logs(key.as_deref(), *last, &sql_config);
+        }
+        Commands::Stats { since, top, format } => {
+            let since = time::parse_since(since, time::now())?;
+            return stats::stats(since, *top, format, &sql_config);
+        }
+        Commands::Cache {
+            command: CacheCommands::Clear,
+        } => {
+            return cache::clear(&sql_config);
+        }
+        Commands::Verify { range, require } => {
+            let cd = identity::current_repo()?;
+            let cd_str = cd.to_string_lossy();
+            let required: Vec<String> = if require.is_empty() {
+                let mut keys = sql_config.list_repo_hooks(&cd_str, &HookTypes::PreCommit)?;
+                keys.extend(sql_config.list_repo_hooks(&cd_str, &HookTypes::PrePush)?);
+                keys.sort();
+                keys.dedup();
+                keys
+            } else {
+                require
+                    .iter()
+                    .map(|name| hook_key(&config_file, name))
+                    .collect()
+            };
+            return notes::verify(&cd, range, &required);
+        }
+        Commands::Prune { dry_run } => {
+            let config = yml_parser::read_config(&config_file)?;
+            return prune::prune(&config, *dry_run, &sql_config);
+        }
+        Commands::Doctor { .. } => unreachable!("handled before the database is opened"),
+        Commands::Adopt { glob_pattern } => {
+            return adopt::adopt_hooks(&config_file, &data_dir, glob_pattern, &sql_config);
         }
     }
     Ok(())
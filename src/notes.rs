@@ -0,0 +1,203 @@
+use git2::{Oid, Repository, Signature, Status, StatusOptions};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{git_hook::GitHook, hook_types::HookTypes};
+
+/// Notes ref holding the hook results
+pub const NOTES_REF: &str = "refs/notes/crab-hooks";
+
+/// One hook result, stored as a line of JSON in the note
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookNote {
+    pub hook: String,
+    pub hook_type: String,
+    pub outcome: String,
+    /// Hash of the hook's config when it ran, see `GitHook::config_hash`
+    pub config_hash: String,
+    /// Version of crab-hooks that ran it
+    pub version: String,
+}
+
+/// Outcome of a hook that passed, the only one recorded so far
+pub const PASSED: &str = "passed";
+
+/// The hook results noted on an object, a commit or a tree
+pub fn read_notes(repo: &Repository, oid: Oid) -> Vec<HookNote> {
+    let Ok(note) = repo.find_note(Some(NOTES_REF), oid) else {
+        return Vec::new();
+    };
+    note.message()
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Add results to the note of an object, replacing older results of the same hooks
+pub fn add_notes(
+    repo: &Repository,
+    oid: Oid,
+    notes: &[HookNote],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut all = read_notes(repo, oid);
+    all.retain(|old| {
+        !notes
+            .iter()
+            .any(|new| new.hook == old.hook && new.hook_type == old.hook_type)
+    });
+    all.extend(notes.iter().cloned());
+    let mut message = String::new();
+    for note in &all {
+        message.push_str(&serde_json::to_string(note)?);
+        message.push('\n');
+    }
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("crab-hooks", "crab-hooks@localhost"))?;
+    repo.note(&signature, &signature, Some(NOTES_REF), oid, &message, true)?;
+    Ok(())
+}
+
+/// The object the results of a hook type are noted on: the staged tree for
+/// pre-commit, which has no commit yet, and HEAD for pre-push
+fn noted_object(repo: &Repository, hook_type: &HookTypes) -> Option<Oid> {
+    match hook_type {
+        HookTypes::PreCommit => repo.index().ok()?.write_tree().ok(),
+        HookTypes::PrePush => repo.head().ok()?.peel_to_commit().ok().map(|c| c.id()),
+        _ => None,
+    }
+}
+
+/// Note the hooks that passed, for the hook types that are noted
+pub fn record(
+    repo_path: &Path,
+    hook_type: &HookTypes,
+    passed: &[&GitHook],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if passed.is_empty() {
+        return Ok(());
+    }
+    let repo = Repository::open(repo_path)?;
+    // The hooks ran on the working tree, which must match what is noted: the
+    // index for pre-commit, HEAD for pre-push
+    let staged_count = !matches!(hook_type, HookTypes::PreCommit);
+    if has_changes(&repo, staged_count) {
+        return Ok(());
+    }
+    let Some(oid) = noted_object(&repo, hook_type) else {
+        return Ok(());
+    };
+    let mut notes = Vec::new();
+    for hook in passed {
+        notes.push(HookNote {
+            hook: hook.key().to_string(),
+            hook_type: hook_type.to_string(),
+            outcome: PASSED.to_string(),
+            config_hash: hook.config_hash()?,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        });
+    }
+    add_notes(&repo, oid, &notes)
+}
+
+/// Whether the working tree differs from HEAD, or only from the index when
+/// `staged_count` is false. Untracked files count as changes.
+fn has_changes(repo: &Repository, staged_count: bool) -> bool {
+    let unstaged = Status::WT_NEW
+        | Status::WT_MODIFIED
+        | Status::WT_DELETED
+        | Status::WT_TYPECHANGE
+        | Status::WT_RENAMED;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true).include_ignored(false);
+    repo.statuses(Some(&mut opts))
+        .map(|s| {
+            s.iter()
+                .any(|e| staged_count || e.status().intersects(unstaged))
+        })
+        .unwrap_or(true)
+}
+
+/// Whether a note proves `hook` already passed, with the same config, on exactly
+/// what HEAD contains: on HEAD itself or on its tree. Uncommitted changes mean
+/// the hook would not run on the same content, so nothing is proven.
+pub fn passed_on_head(repo_path: &Path, hook: &GitHook) -> bool {
+    let Ok(repo) = Repository::open(repo_path) else {
+        return false;
+    };
+    let Ok(commit) = repo.head().and_then(|h| h.peel_to_commit()) else {
+        return false;
+    };
+    let Ok(hash) = hook.config_hash() else {
+        return false;
+    };
+    if has_changes(&repo, true) {
+        return false;
+    }
+    [commit.id(), commit.tree_id()].into_iter().any(|oid| {
+        read_notes(&repo, oid)
+            .iter()
+            .any(|n| n.hook == hook.key() && n.outcome == PASSED && n.config_hash == hash)
+    })
+}
+
+/// The required hooks without a passing note on a commit or on its tree
+pub fn missing_hooks(
+    repo: &Repository,
+    commit: Oid,
+    required: &[String],
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let tree = repo.find_commit(commit)?.tree_id();
+    let mut notes = read_notes(repo, commit);
+    notes.extend(read_notes(repo, tree));
+    Ok(required
+        .iter()
+        .filter(|hook| {
+            !notes
+                .iter()
+                .any(|n| &n.hook == *hook && n.outcome == PASSED)
+        })
+        .cloned()
+        .collect())
+}
+
+/// Check that every commit in `range` (`A..B`, or a single revision for its whole
+/// history) carries passing notes for the required hooks
+pub fn verify(
+    repo_path: &Path,
+    range: &str,
+    required: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if required.is_empty() {
+        return Err("No required hooks, pass them with --require".into());
+    }
+    let repo = Repository::open(repo_path)?;
+    let mut walk = repo.revwalk()?;
+    if range.contains("..") {
+        walk.push_range(range)?;
+    } else {
+        walk.push(repo.revparse_single(range)?.peel_to_commit()?.id())?;
+    }
+
+    let mut checked = 0;
+    let mut failed = 0;
+    for oid in walk {
+        let oid = oid?;
+        checked += 1;
+        let missing = missing_hooks(&repo, oid, required)?;
+        if !missing.is_empty() {
+            failed += 1;
+            println!("{} missing: {}", oid, missing.join(", "));
+        }
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} commit(s) lack passing notes for the required hooks",
+            failed, checked
+        )
+        .into());
+    }
+    println!("All {} commit(s) passed {}", checked, required.join(", "));
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::{path::Path, str::FromStr};
+
+use crate::{
+    git_hook::{self, ApplyOptions},
+    hook_types::HookTypes,
+    sqllite::SqlLiteConfig,
+    yml_parser::Config,
+};
+
+/// Bring a repo in line with a profile.
+///
+/// Installs the hooks of the profile that are missing, and removes hooks this
+/// profile installed earlier that are no longer part of it. Hooks that were
+/// already installed by hand are left alone.
+pub fn apply_profile(
+    repo: &Path,
+    profile_name: &str,
+    config: &Config,
+    options: &ApplyOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let profile = config
+        .profiles
+        .get(profile_name)
+        .ok_or_else(|| format!("No profile named {} in the config", profile_name))?;
+    let repo_str = repo.to_str().expect("Repo path is not valid UTF-8");
+
+    let mut wanted = Vec::new();
+    for (hook_type, names) in profile {
+        let hook_type = HookTypes::from_str(hook_type).map_err(|_| {
+            format!(
+                "Unknown hook type {} in profile {}",
+                hook_type, profile_name
+            )
+        })?;
+        for name in names {
+            wanted.push((hook_type.clone(), name.as_str()));
+        }
+    }
+
+    // Drop what the profile no longer contains
+    for installed in sql_config.list_profile_hooks(repo_str, profile_name)? {
+        let hook_type = HookTypes::from_str(&installed.hook_type)
+            .map_err(|_| format!("Unknown hook type {} in the database", installed.hook_type))?;
+        let still_wanted = wanted
+            .iter()
+            .any(|(t, name)| *t == hook_type && config.key_of(name) == installed.name);
+        if !still_wanted {
+            println!(
+                "Removing {} as {}, no longer part of profile {}",
+                installed.name, hook_type, profile_name
+            );
+            git_hook::uninstall_hook(&installed.name, repo, &hook_type, &options.shim, sql_config)?;
+        }
+    }
+
+    for (hook_type, name) in &wanted {
+        if sql_config.check_if_hook_is_same(repo_str, hook_type, config.key_of(name))? {
+            continue;
+        }
+        let hook = config
+            .find_hook(name)
+            .ok_or_else(|| format!("No hook named {} in the config", name))?;
+        hook.apply_hook(repo, hook_type, options, sql_config)?;
+        sql_config.set_hook_profile(repo_str, hook_type, hook.key(), profile_name)?;
+    }
+
+    sql_config.add_repo_profile(repo_str, profile_name)?;
+    Ok(())
+}
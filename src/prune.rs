@@ -0,0 +1,149 @@
+use std::{fmt, path::Path};
+
+use crate::{
+    identity::{self, RepoIdentity},
+    repos,
+    sqllite::SqlLiteConfig,
+    yml_parser::Config,
+};
+
+/// Database rows that no longer describe anything real
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stale {
+    /// The repo was deleted or moved
+    Repo(String),
+    /// The hook file no longer runs crab-hooks, it was removed or edited by hand
+    HookType { repo: String, hook_type: String },
+    /// The hook is neither in the config nor installed anywhere
+    Hook(String),
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stale::Repo(repo) => write!(f, "repo {} (no longer exists)", repo),
+            Stale::HookType { repo, hook_type } => write!(
+                f,
+                "{} hooks of {} (the hook file no longer runs crab-hooks)",
+                hook_type, repo
+            ),
+            Stale::Hook(name) => write!(f, "hook {} (not in the config)", name),
+        }
+    }
+}
+
+/// Repos that no longer exist but share their identity with a known repo that
+/// does, most likely moved there, as (old path, new path). They are kept for
+/// `relocate`, which needs their rows.
+pub fn find_moved(
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let existing: Vec<(String, RepoIdentity)> = sql_config
+        .list_known_repos()?
+        .into_iter()
+        .filter(|repo| Path::new(repo).join(".git").is_dir())
+        .filter_map(|repo| {
+            identity::identify(Path::new(&repo))
+                .ok()
+                .map(|id| (repo, id))
+        })
+        .collect();
+    let mut moved = Vec::new();
+    for (repo, id) in sql_config.list_repo_identities()? {
+        if id.root_commit.is_none() || Path::new(&repo).join(".git").is_dir() {
+            continue;
+        }
+        if let Some((to, _)) = existing.iter().find(|(_, other)| *other == id) {
+            moved.push((repo, to.clone()));
+        }
+    }
+    Ok(moved)
+}
+
+/// Find everything `prune` would delete
+pub fn find_stale(
+    config: &Config,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<Stale>, Box<dyn std::error::Error>> {
+    let moved = find_moved(sql_config)?;
+    let mut stale = Vec::new();
+    let mut gone_repos = Vec::new();
+    for repo in sql_config.list_known_repos()? {
+        if moved.iter().any(|(from, _)| *from == repo) {
+            continue;
+        }
+        if !Path::new(&repo).join(".git").is_dir() {
+            gone_repos.push(repo.clone());
+            stale.push(Stale::Repo(repo));
+        }
+    }
+
+    let mut dropped_types = Vec::new();
+    for (repo, hook_type) in sql_config.list_installs()? {
+        if gone_repos.contains(&repo) || moved.iter().any(|(from, _)| *from == repo) {
+            continue;
+        }
+        if !repos::script_is_managed(Path::new(&repo), &hook_type) {
+            dropped_types.push((repo.clone(), hook_type.clone()));
+            stale.push(Stale::HookType { repo, hook_type });
+        }
+    }
+
+    // Hooks still installed somewhere are left to `doctor --fix`, which also fixes the script
+    let installs = sql_config.list_all_repo_hooks()?;
+    for name in sql_config.list_hook_names()? {
+        if config.find_hook_by_key(&name).is_some() {
+            continue;
+        }
+        let in_use = installs.iter().any(|r| {
+            r.name == name
+                && !gone_repos.contains(&r.repo)
+                && !dropped_types.contains(&(r.repo.clone(), r.hook_type.clone()))
+        });
+        if !in_use {
+            stale.push(Stale::Hook(name));
+        }
+    }
+    Ok(stale)
+}
+
+/// Print the stale rows, and delete them in one transaction unless `dry_run`
+pub fn prune(
+    config: &Config,
+    dry_run: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (from, to) in find_moved(sql_config)? {
+        println!(
+            "Keeping {}, it looks moved to {}: run `crab-hooks relocate {} --from {}`",
+            from, to, to, from
+        );
+    }
+    let stale = find_stale(config, sql_config)?;
+    if stale.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    println!("Stale entries:");
+    for entry in &stale {
+        println!(" - {}", entry);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    sql_config.transaction(|| {
+        for entry in &stale {
+            match entry {
+                Stale::Repo(repo) => sql_config.forget_repo(repo)?,
+                Stale::HookType { repo, hook_type } => {
+                    sql_config.forget_hook_type(repo, hook_type)?
+                }
+                Stale::Hook(name) => sql_config.forget_hook(name)?,
+            }
+        }
+        Ok(())
+    })?;
+    println!("Pruned {} entries", stale.len());
+    Ok(())
+}
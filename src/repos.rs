@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::{fs, path::Path, str::FromStr};
+
+use crate::{
+    hook_types::HookTypes,
+    shim,
+    sqllite::{RepoHook, SqlLiteConfig},
+    time,
+};
+
+/// The hooks installed in a repo as one hook type
+#[derive(Debug, Serialize)]
+pub struct HookTypeInventory {
+    pub hook_type: String,
+    pub hooks: Vec<String>,
+    /// The hooks switched off by `disable`, with when they turn back on if set
+    pub disabled: Vec<DisabledHook>,
+    /// Whether the hook file still is the crab-hooks script
+    pub script_ok: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisabledHook {
+    pub name: String,
+    pub until: Option<i64>,
+}
+
+/// Everything crab-hooks has installed in one repo
+#[derive(Debug, Serialize)]
+pub struct RepoInventory {
+    pub path: String,
+    /// Whether the repo is still a git repository on disk
+    pub exists: bool,
+    pub hook_types: Vec<HookTypeInventory>,
+}
+
+impl RepoInventory {
+    pub fn is_healthy(&self) -> bool {
+        self.exists && self.hook_types.iter().all(|t| t.script_ok)
+    }
+}
+
+/// Whether the hook file of an install still hands over to crab-hooks
+pub fn script_is_managed(repo: &Path, hook_type: &str) -> bool {
+    let Ok(hook_type) = HookTypes::from_str(hook_type) else {
+        return false;
+    };
+    fs::read_to_string(shim::hook_path(repo, &hook_type)).is_ok_and(|c| shim::is_shim(&c))
+}
+
+/// Group the `repo_hooks` rows by repo and hook type, keeping only the matching hooks
+pub fn inventory(
+    hook_name: Option<&str>,
+    hook_type: Option<&HookTypes>,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<RepoInventory>, Box<dyn std::error::Error>> {
+    let hook_type = hook_type.map(|t| t.to_string());
+    let rows: Vec<RepoHook> = sql_config
+        .list_all_repo_hooks()?
+        .into_iter()
+        .filter(|r| hook_name.is_none_or(|n| r.name == n))
+        .filter(|r| hook_type.as_ref().is_none_or(|t| &r.hook_type == t))
+        .collect();
+
+    // Rows are sorted by repo and type, so groups are consecutive
+    let now = time::now();
+    let mut repos: Vec<RepoInventory> = Vec::new();
+    for row in rows {
+        let disabled = row.is_disabled(now).then(|| DisabledHook {
+            name: row.name.clone(),
+            until: row.disabled_until,
+        });
+        if repos.last().is_none_or(|r| r.path != row.repo) {
+            let path = Path::new(&row.repo);
+            repos.push(RepoInventory {
+                path: row.repo.clone(),
+                exists: path.join(".git").is_dir(),
+                hook_types: Vec::new(),
+            });
+        }
+        let repo = repos.last_mut().expect("just pushed");
+        match repo.hook_types.last_mut() {
+            Some(t) if t.hook_type == row.hook_type => {
+                t.hooks.push(row.name);
+                t.disabled.extend(disabled);
+            }
+            _ => {
+                let script_ok =
+                    repo.exists && script_is_managed(Path::new(&repo.path), &row.hook_type);
+                repo.hook_types.push(HookTypeInventory {
+                    hook_type: row.hook_type,
+                    hooks: vec![row.name],
+                    disabled: disabled.into_iter().collect(),
+                    script_ok,
+                });
+            }
+        }
+    }
+    Ok(repos)
+}
+
+pub fn list_repos(
+    hook_name: Option<&str>,
+    hook_type: Option<&HookTypes>,
+    json: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repos = inventory(hook_name, hook_type, sql_config)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&repos)?);
+        return Ok(());
+    }
+
+    if repos.is_empty() {
+        println!("No managed repositories found");
+        return Ok(());
+    }
+    for repo in &repos {
+        if repo.exists {
+            println!("{}", repo.path);
+        } else {
+            println!("{} [missing]", repo.path);
+        }
+        for t in &repo.hook_types {
+            let flag = if repo.exists && !t.script_ok {
+                " [hook file is missing or no longer managed]"
+            } else {
+                ""
+            };
+            let hooks: Vec<String> = t
+                .hooks
+                .iter()
+                .map(|name| match t.disabled.iter().find(|d| &d.name == name) {
+                    Some(DisabledHook {
+                        until: Some(until), ..
+                    }) => format!("{} [disabled until {}]", name, time::format_time(*until)),
+                    Some(_) => format!("{} [disabled]", name),
+                    None => name.clone(),
+                })
+                .collect();
+            println!("  {}: {}{}", t.hook_type, hooks.join(", "), flag);
+        }
+    }
+    Ok(())
+}
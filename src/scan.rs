@@ -0,0 +1,139 @@
+use git2::Repository;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{hook_types::HookTypes, identity, shim, sqllite::SqlLiteConfig};
+
+/// Directories that never contain repositories worth managing
+const SKIPPED_DIRS: [&str; 2] = ["node_modules", "target"];
+
+/// The hooks found in one repository
+#[derive(Debug, Default)]
+pub struct ScannedRepo {
+    pub path: PathBuf,
+    /// Hook types whose script was written by crab-hooks
+    pub managed: Vec<String>,
+    /// Hook types with a script crab-hooks does not manage
+    pub unmanaged: Vec<String>,
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_home(dir: &str) -> PathBuf {
+    match dir.strip_prefix('~') {
+        Some(rest) => {
+            let home = home::home_dir().expect("Could not find HOME directory");
+            home.join(rest.trim_start_matches('/'))
+        }
+        None => PathBuf::from(dir),
+    }
+}
+
+/// Walk `dir` up to `max_depth` levels deep and collect every git repository.
+///
+/// Hidden folders, `node_modules` and `target` are skipped, and so is everything
+/// inside a repository once it is found.
+pub fn find_repos(dir: &Path, max_depth: usize) -> Vec<ScannedRepo> {
+    let mut repos = Vec::new();
+    walk(dir, max_depth, &mut repos);
+    repos.sort_by(|a, b| a.path.cmp(&b.path));
+    repos
+}
+
+fn walk(dir: &Path, depth: usize, repos: &mut Vec<ScannedRepo>) {
+    if dir.join(".git").exists() {
+        if let Ok(repo) = Repository::open(dir) {
+            repos.push(scan_repo(dir, repo.path()));
+            return;
+        }
+    }
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.map_while(Result::ok) {
+        // file_type does not follow symlinks, so links never loop
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+        walk(&entry.path(), depth - 1, repos);
+    }
+}
+
+fn scan_repo(workdir: &Path, git_dir: &Path) -> ScannedRepo {
+    let mut scanned = ScannedRepo {
+        path: workdir.to_path_buf(),
+        ..ScannedRepo::default()
+    };
+    let Ok(entries) = fs::read_dir(git_dir.join("hooks")) else {
+        return scanned;
+    };
+    let mut names: Vec<String> = entries
+        .map_while(Result::ok)
+        .filter(|e| e.path().is_file())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| HookTypes::from_str(n).is_ok())
+        .collect();
+    names.sort();
+    for name in names {
+        let content = fs::read_to_string(git_dir.join("hooks").join(&name)).unwrap_or_default();
+        if shim::is_shim(&content) {
+            scanned.managed.push(name);
+        } else {
+            scanned.unmanaged.push(name);
+        }
+    }
+    scanned
+}
+
+pub fn scan(
+    dir: &str,
+    max_depth: usize,
+    register: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(expand_home(dir))?;
+    let repos = find_repos(&dir, max_depth);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    for repo in &repos {
+        let status = match (repo.managed.is_empty(), repo.unmanaged.is_empty()) {
+            (true, true) => "no hooks".to_string(),
+            (false, true) => format!("managed: {}", repo.managed.join(", ")),
+            (true, false) => format!("unmanaged: {}", repo.unmanaged.join(", ")),
+            (false, false) => format!(
+                "managed: {}; unmanaged: {}",
+                repo.managed.join(", "),
+                repo.unmanaged.join(", ")
+            ),
+        };
+        println!("{} ({})", repo.path.display(), status);
+        if register {
+            sql_config.register_repo(&repo.path.to_string_lossy(), now)?;
+            identity::remember(&repo.path, sql_config)?;
+        }
+    }
+
+    let count = |f: fn(&ScannedRepo) -> bool| repos.iter().filter(|r| f(r)).count();
+    println!(
+        "Found {} repositories: {} with managed hooks, {} with unmanaged hooks, {} without hooks",
+        repos.len(),
+        count(|r| !r.managed.is_empty()),
+        count(|r| !r.unmanaged.is_empty()),
+        count(|r| r.managed.is_empty() && r.unmanaged.is_empty()),
+    );
+    if register {
+        println!("Registered {} repositories", repos.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use std::{
+    fs::{self, set_permissions},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{hook_types::HookTypes, sqllite::SqlLiteConfig, yml_parser};
+
+/// First line after the shebang of every hook script written by crab-hooks
+pub const SHIM_MARKER: &str = "# Managed by crab-hooks, changes will be overwritten";
+
+/// Suffix given to an unmanaged hook script when it is chained in front of crab-hooks
+pub const LEGACY_SUFFIX: &str = ".legacy";
+
+/// Name of the installed binary, looked up on PATH by the shim
+pub const BIN_NAME: &str = "crab-hooks";
+
+pub fn hook_path(repo: &Path, hook_type: &HookTypes) -> PathBuf {
+    repo.join(".git/hooks").join(hook_type.to_string())
+}
+
+pub fn legacy_hook_path(repo: &Path, hook_type: &HookTypes) -> PathBuf {
+    repo.join(".git/hooks")
+        .join(format!("{}{}", hook_type, LEGACY_SUFFIX))
+}
+
+/// Whether a hook script was generated by crab-hooks
+pub fn is_shim(content: &str) -> bool {
+    content.lines().any(|line| line == SHIM_MARKER)
+}
+
+/// Format version of the generated hook scripts, bump it whenever `shim_contents` changes
+pub const SHIM_VERSION: u32 = 1;
+
+/// Environment variable the shim uses to tell `dispatch` which format it was written in
+pub const SHIM_VERSION_ENV: &str = "CRAB_HOOKS_SHIM_VERSION";
+
+const SHIM_VERSION_PREFIX: &str = "# crab-hooks shim version: ";
+
+/// The format version embedded in a hook script, scripts from before versioning have none
+pub fn shim_version(content: &str) -> Option<u32> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix(SHIM_VERSION_PREFIX))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// The fallback binary recorded in a hook script by `{{crab_hooks}}`
+pub fn recorded_binary(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| {
+        line.strip_prefix("if [ -z \"$CRAB_HOOKS_BIN\" ]; then CRAB_HOOKS_BIN=\"")
+            .and_then(|rest| rest.strip_suffix("\"; fi"))
+    })
+}
+
+/// Warn when the calling shim was written by an older or newer crab-hooks
+pub fn check_shim_version() {
+    let version = std::env::var(SHIM_VERSION_ENV)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0);
+    if version < SHIM_VERSION {
+        eprintln!(
+            "crab-hooks: this hook script is outdated (format {}, current {}), run `crab-hooks repair` to regenerate it",
+            version, SHIM_VERSION
+        );
+    } else if version > SHIM_VERSION {
+        eprintln!(
+            "crab-hooks: this hook script was written by a newer crab-hooks (format {}, supported {}), please upgrade",
+            version, SHIM_VERSION
+        );
+    }
+}
+
+/// Template used when the config does not set `shim_template`
+pub const DEFAULT_TEMPLATE: &str = "#!/usr/bin/env sh\n{{crab_hooks}}\n";
+
+/// Variables available in a shim template
+pub const TEMPLATE_VARIABLES: [&str; 5] = [
+    "crab_hooks",
+    "binary",
+    "hook_type",
+    "hook_names",
+    "repo_root",
+];
+
+/// How the hook scripts of an install are written
+#[derive(Debug, Clone, Default)]
+pub struct ShimOptions {
+    /// Template from the config, `DEFAULT_TEMPLATE` when unset
+    pub template: Option<String>,
+    /// Config the hooks were installed from, handed to `dispatch` by the script.
+    /// When unset `dispatch` reads the default config.
+    pub config_file: Option<PathBuf>,
+}
+
+impl ShimOptions {
+    /// The shim options of installs made from `config_file`
+    pub fn load(config_file: &Path) -> Result<ShimOptions, Box<dyn std::error::Error>> {
+        Ok(ShimOptions {
+            template: yml_parser::read_config(config_file)?.load_shim_template()?,
+            config_file: Some(std::path::absolute(config_file)?),
+        })
+    }
+}
+
+/// What a hook script is rendered from
+pub struct ShimContext<'a> {
+    /// Binary recorded at install time, the fallback when crab-hooks is not on PATH
+    pub exe: &'a str,
+    pub hook_type: &'a HookTypes,
+    /// Whether an unmanaged `<type>.legacy` script runs first
+    pub chained: bool,
+    pub hook_names: &'a [String],
+    pub repo_root: &'a Path,
+    /// Passed to `dispatch` as `--config-file`
+    pub config_file: Option<&'a Path>,
+}
+
+/// The hook script installed for a hook type, rendered from the default template
+pub fn shim_contents(exe: &str, hook_type: &HookTypes, chained: bool) -> String {
+    render_shim(
+        DEFAULT_TEMPLATE,
+        &ShimContext {
+            exe,
+            hook_type,
+            chained,
+            hook_names: &[],
+            repo_root: Path::new("."),
+            config_file: None,
+        },
+    )
+}
+
+/// Render a hook script from a template.
+///
+/// `{{crab_hooks}}` expands to the block that runs the hooks. It does not name
+/// any hooks, `crab-hooks dispatch` looks those up in the database. The binary
+/// is looked up on PATH first, so the script keeps working when crab-hooks is
+/// reinstalled elsewhere; `exe` is only the fallback. If neither exists the
+/// hooks are skipped with a warning, or the git command fails when
+/// `CRAB_HOOKS_MISSING=fail`.
+pub fn render_shim(template: &str, ctx: &ShimContext) -> String {
+    let body = template
+        .replace("{{crab_hooks}}", &invocation(ctx))
+        .replace("{{binary}}", ctx.exe)
+        .replace("{{hook_type}}", &ctx.hook_type.to_string())
+        .replace("{{hook_names}}", &ctx.hook_names.join(" "))
+        .replace("{{repo_root}}", &ctx.repo_root.to_string_lossy());
+
+    // The marker and version always follow the shebang, whatever the template says,
+    // and so does the export telling `dispatch` the version, so templates calling
+    // `{{binary}} dispatch` themselves are not reported as outdated
+    let (shebang, rest) = match body.strip_prefix("#!") {
+        Some(_) => body.split_once('\n').unwrap_or((body.as_str(), "")),
+        None => ("#!/usr/bin/env sh", body.as_str()),
+    };
+    let mut content = format!(
+        "{}\n{}\n{}{}\nexport {}={}\n{}",
+        shebang,
+        SHIM_MARKER,
+        SHIM_VERSION_PREFIX,
+        SHIM_VERSION,
+        SHIM_VERSION_ENV,
+        SHIM_VERSION,
+        rest
+    );
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content
+}
+
+fn invocation(ctx: &ShimContext) -> String {
+    let mut lines = vec!["set -e".to_string()];
+    let mut stdin = "";
+    if ctx.chained {
+        // Run the previous hook first, handing it the same argv and stdin
+        lines.push("CRAB_HOOKS_STDIN=$(mktemp)".to_string());
+        lines.push("trap 'rm -f \"$CRAB_HOOKS_STDIN\"' EXIT".to_string());
+        lines.push("cat > \"$CRAB_HOOKS_STDIN\"".to_string());
+        lines.push(format!(
+            "\"$(dirname \"$0\")/{}{}\" \"$@\" < \"$CRAB_HOOKS_STDIN\"",
+            ctx.hook_type, LEGACY_SUFFIX
+        ));
+        stdin = " < \"$CRAB_HOOKS_STDIN\"";
+    }
+    lines.extend([
+        format!("CRAB_HOOKS_BIN=$(command -v {} || true)", BIN_NAME),
+        format!(
+            "if [ -z \"$CRAB_HOOKS_BIN\" ]; then CRAB_HOOKS_BIN=\"{}\"; fi",
+            ctx.exe
+        ),
+        "if [ ! -x \"$CRAB_HOOKS_BIN\" ]; then".to_string(),
+        format!(
+            "  echo \"crab-hooks: binary not found, {} hooks were not run\" >&2",
+            ctx.hook_type
+        ),
+        "  if [ \"${CRAB_HOOKS_MISSING:-skip}\" = \"fail\" ]; then exit 1; fi".to_string(),
+        "  echo \"crab-hooks: skipping, set CRAB_HOOKS_MISSING=fail to block instead\" >&2"
+            .to_string(),
+        "  exit 0".to_string(),
+        "fi".to_string(),
+    ]);
+    let exec = if ctx.chained { "" } else { "exec " };
+    let config = match ctx.config_file {
+        Some(path) => format!(" --config-file \"{}\"", path.display()),
+        None => String::new(),
+    };
+    lines.push(format!(
+        "{}\"$CRAB_HOOKS_BIN\"{} dispatch {} \"$@\"{}",
+        exec, config, ctx.hook_type, stdin
+    ));
+    lines.join("\n")
+}
+
+/// Check that a template only uses known variables and still hands over to crab-hooks
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("unterminated `{{`".into());
+        };
+        let name = rest[start + 2..start + end].trim();
+        if !TEMPLATE_VARIABLES.contains(&name) {
+            return Err(format!(
+                "unknown variable `{{{{{}}}}}`, expected one of {:?}",
+                name, TEMPLATE_VARIABLES
+            ));
+        }
+        rest = &rest[start + end + 2..];
+    }
+
+    let invokes_crab_hooks = template.contains("{{crab_hooks}}")
+        || template
+            .lines()
+            .any(|l| l.contains("{{binary}}") && l.contains("dispatch"));
+    if !invokes_crab_hooks {
+        return Err(
+            "must include `{{crab_hooks}}`, or call `{{binary}} dispatch {{hook_type}} \"$@\"`"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// Write the shim for a hook type in `repo_root` and make it executable.
+///
+/// The installed hooks are read from `repo_hooks`, so call this after updating it.
+pub fn write_shim(
+    repo_root: &Path,
+    hook_type: &HookTypes,
+    options: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let exe_location = std::env::current_exe()?;
+    let hook_names = sql_config.list_repo_hooks(&repo_root.to_string_lossy(), hook_type)?;
+    let ctx = ShimContext {
+        exe: exe_location
+            .to_str()
+            .expect("Binary path is not valid UTF-8"),
+        hook_type,
+        chained: legacy_hook_path(repo_root, hook_type).exists(),
+        hook_names: &hook_names,
+        repo_root,
+        config_file: options.config_file.as_deref(),
+    };
+    let content = render_shim(
+        options.template.as_deref().unwrap_or(DEFAULT_TEMPLATE),
+        &ctx,
+    );
+
+    let file_path = hook_path(repo_root, hook_type);
+    fs::write(&file_path, content)?;
+
+    let mut permissions = fs::metadata(&file_path)?.permissions();
+    permissions.set_mode(0o755);
+    set_permissions(&file_path, permissions)?;
+    Ok(())
+}
+
+/// Rewrite the shim of every install recorded in `repo_hooks` to point at the current binary
+pub fn repair(
+    options: &ShimOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut repaired = 0;
+    let mut failed = 0;
+    for (repo, hook_type) in sql_config.list_installs()? {
+        let repo_path = Path::new(&repo);
+        let Ok(hook_type) = HookTypes::from_str(&hook_type) else {
+            eprintln!("Skipping {}: unknown hook type {}", repo, hook_type);
+            failed += 1;
+            continue;
+        };
+        if !repo_path.join(".git").is_dir() {
+            eprintln!("Skipping {}: not a git repository anymore", repo);
+            failed += 1;
+            continue;
+        }
+
+        let file_path = hook_path(repo_path, &hook_type);
+        match write_shim(repo_path, &hook_type, options, sql_config) {
+            Ok(()) => {
+                println!("Repaired {}", file_path.display());
+                repaired += 1;
+            }
+            Err(e) => {
+                eprintln!("Failed to repair {}: {}", file_path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    println!("Repaired {} hook script(s), {} skipped", repaired, failed);
+    Ok(())
+}
@@ -1,6 +1,146 @@
-use sqlite::{Connection, State};
+use sqlite::{Connection, OpenFlags, State, Statement};
 
-use crate::hook_types::HookTypes;
+use crate::{cache::CacheKey, hook_types::HookTypes, identity::RepoIdentity};
+
+/// One row of `repo_hooks`: a hook installed in a repo as a hook type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoHook {
+    pub repo: String,
+    pub name: String,
+    pub hook_type: String,
+    /// Set by `disable`, the hook stays installed but does not run
+    pub disabled: bool,
+    /// When a disabled hook turns itself back on, as a unix timestamp
+    pub disabled_until: Option<i64>,
+}
+
+impl RepoHook {
+    /// Whether the hook is switched off at `now`, an expired disable no longer counts
+    pub fn is_disabled(&self, now: i64) -> bool {
+        self.disabled && self.disabled_until.is_none_or(|until| until > now)
+    }
+}
+
+/// How often a hook ran, and how often it was skipped on request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookCounts {
+    pub total_runs: i64,
+    pub successful_runs: i64,
+    pub skipped_runs: i64,
+}
+
+/// A saved copy of a hook file that was overwritten by `apply-hook --force`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub id: i64,
+    pub repo: String,
+    pub hook_type: String,
+    pub path: String,
+    pub created_at: i64,
+}
+
+/// One row of `runs`, a single run of a hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunRecord {
+    pub id: i64,
+    pub hook: String,
+    pub repo: Option<String>,
+    /// `None` when started by `crab-hooks run` rather than a hook script
+    pub hook_type: Option<String>,
+    /// HEAD when the hook ran, `None` before the first commit
+    pub head: Option<String>,
+    pub started_at: i64,
+    pub duration_ms: i64,
+    pub exit_code: Option<i64>,
+    /// Signal that killed the command, on unix
+    pub signal: Option<i64>,
+    /// See `history::RunStatus`
+    pub outcome: String,
+    /// The config, and content and input, the hook ran on, see `cache::CacheKey`
+    pub config_hash: Option<String>,
+    pub content_key: Option<String>,
+    /// 1 for the first try, higher for retries of a flaky hook
+    pub attempt: i64,
+}
+
+/// Output of a failed run, one row of `run_logs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunLog {
+    pub run: RunRecord,
+    /// zlib compressed, see `logs::compress`
+    pub output: Vec<u8>,
+    /// Uncompressed size in bytes
+    pub size: i64,
+    /// Only the tail was kept, see `logs::LOG_LIMIT`
+    pub truncated: bool,
+}
+
+/// Which runs `list_runs` returns, every field left unset matches everything
+#[derive(Debug, Clone, Default)]
+pub struct RunFilter {
+    pub hook: Option<String>,
+    pub repo: Option<String>,
+    pub hook_type: Option<String>,
+    pub outcome: Option<String>,
+    /// Only runs started at or after this unix timestamp
+    pub since: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// One schema change, in SQL or, when it needs more than SQL, in Rust
+enum Migration {
+    Sql(&'static str),
+    Rust(fn(&SqlLiteConfig) -> Result<(), Box<dyn std::error::Error>>),
+}
+
+/// Schema changes applied on top of the tables created in `init`, in order.
+/// The number applied so far is kept in `PRAGMA user_version`.
+const MIGRATIONS: &[Migration] = &[
+    // 1: hooks installed by a profile remember it, so `apply-profile --update` can remove them
+    Migration::Sql("ALTER TABLE repo_hooks ADD COLUMN profile TEXT;
+     CREATE TABLE IF NOT EXISTS repo_profiles (repo TEXT, profile TEXT, UNIQUE(repo, profile));"),
+    // 2: what a repo is, independently of its path, so moved checkouts can be found again
+    Migration::Sql("CREATE TABLE IF NOT EXISTS repo_identities (repo TEXT UNIQUE, root_commit TEXT, remote TEXT);"),
+    // 3: hooks can be switched off for a while without uninstalling them
+    Migration::Sql("ALTER TABLE repo_hooks ADD COLUMN disabled INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE repo_hooks ADD COLUMN disabled_until INTEGER;"),
+    // 4: runs skipped on request are counted apart from the ones that ran
+    Migration::Sql("ALTER TABLE hooks ADD COLUMN skipped_runs INTEGER NOT NULL DEFAULT 0;"),
+    // 5: passing results by hook config, content and input, so nothing is checked twice
+    Migration::Sql("CREATE TABLE IF NOT EXISTS result_cache (repo TEXT, hook TEXT, config_hash TEXT, content_key TEXT, input TEXT, created_at INTEGER,
+        UNIQUE(repo, hook, config_hash, content_key, input));"),
+    // 6: every run of a hook, for `history` and `stats`
+    Migration::Sql("CREATE TABLE IF NOT EXISTS runs (id INTEGER PRIMARY KEY, hook TEXT, repo TEXT, type TEXT, head TEXT,
+        started_at INTEGER, duration_ms INTEGER, exit_code INTEGER, signal INTEGER, outcome TEXT);
+     CREATE INDEX IF NOT EXISTS runs_by_start ON runs (started_at);"),
+    // 7: what a run looked at, so differing outcomes on the same input show a flaky hook
+    Migration::Sql("ALTER TABLE runs ADD COLUMN config_hash TEXT;
+     ALTER TABLE runs ADD COLUMN content_key TEXT;
+     ALTER TABLE runs ADD COLUMN attempt INTEGER NOT NULL DEFAULT 1;"),
+    // 8: what failed runs printed, so it is not lost when a GUI client hides it
+    Migration::Sql("CREATE TABLE IF NOT EXISTS run_logs (run_id INTEGER PRIMARY KEY, output BLOB, size INTEGER, truncated INTEGER);"),
+    // 9: repo paths recorded before they were canonicalized, e.g. through a symlink
+    Migration::Rust(canonicalize_repo_paths),
+];
+
+/// Move the rows of every repo path that exists but is not canonical to its canonical path
+fn canonicalize_repo_paths(sql_config: &SqlLiteConfig) -> Result<(), Box<dyn std::error::Error>> {
+    for repo in sql_config.list_known_repos()? {
+        match std::fs::canonicalize(&repo) {
+            Ok(canonical) if canonical.to_string_lossy() != repo => {
+                sql_config.relocate_repo(&repo, &canonical.to_string_lossy())?
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Columns of `runs` in the order `read_run` expects them
+const RUN_COLUMNS: &str = "runs.id, hook, repo, type, head, started_at, duration_ms, exit_code, signal, outcome, config_hash, content_key, attempt";
+
+/// Schema version of a fully migrated database
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
 
 pub struct SqlLiteConfig {
     // path: String,
@@ -17,16 +157,52 @@ impl SqlLiteConfig {
         config.init()?;
         Ok(config)
     }
+
+    /// Open an existing database as it is, without creating or migrating it
+    pub fn open_read_only(path: &str) -> Result<SqlLiteConfig, Box<dyn std::error::Error>> {
+        let connection = Connection::open_with_flags(path, OpenFlags::new().with_read_only())?;
+        Ok(SqlLiteConfig { connection })
+    }
     fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
         let mut query =
             "CREATE TABLE IF NOT EXISTS hooks (name TEXT UNIQUE, total_runs INTEGER, succesful_runs INTEGER)";
         self.connection.execute(query)?;
         query = "CREATE TABLE IF NOT EXISTS repo_hooks (name TEXT, repo TEXT, type TEXT, FOREIGN KEY(name) REFERENCES hooks(name))";
         self.connection.execute(query)?;
+        query = "CREATE TABLE IF NOT EXISTS repos (path TEXT UNIQUE, registered_at INTEGER)";
+        self.connection.execute(query)?;
+        query = "CREATE TABLE IF NOT EXISTS backups (id INTEGER PRIMARY KEY, repo TEXT, type TEXT, path TEXT, created_at INTEGER)";
+        self.connection.execute(query)?;
 
+        self.migrate()
+    }
+
+    fn migrate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let version = self.schema_version()?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            match migration {
+                Migration::Sql(sql) => self.connection.execute(format!(
+                    "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                    sql,
+                    i + 1
+                ))?,
+                Migration::Rust(step) => self.transaction(|| {
+                    step(self)?;
+                    self.connection
+                        .execute(format!("PRAGMA user_version = {}", i + 1))?;
+                    Ok(())
+                })?,
+            }
+        }
         Ok(())
     }
 
+    pub fn schema_version(&self) -> Result<i64, Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare("PRAGMA user_version")?;
+        statement.next()?;
+        Ok(statement.read::<i64, _>(0)?)
+    }
+
     pub fn add_hook(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
         let query =
             "INSERT OR IGNORE INTO hooks (name, total_runs, succesful_runs) VALUES (?, 0, 0)";
@@ -54,7 +230,7 @@ impl SqlLiteConfig {
         repo: &str,
         hook_type: &HookTypes,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let query = "INSERT INTO repo_hooks VALUES (?, ?, ?)";
+        let query = "INSERT INTO repo_hooks (name, repo, type) VALUES (?, ?, ?)";
         let mut statement = self.connection.prepare(query)?;
         statement.bind((1, name))?;
         statement.bind((2, repo))?;
@@ -80,6 +256,54 @@ impl SqlLiteConfig {
         Ok(false)
     }
 
+    /// Names of the hooks installed in a repo as the given type, in install order
+    pub fn list_repo_hooks(
+        &self,
+        repo: &str,
+        hook_type: &HookTypes,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let query = "SELECT name FROM repo_hooks WHERE repo = ? AND type = ? ORDER BY rowid";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, hook_type.to_string().as_str()))?;
+        let mut names = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            names.push(statement.read::<String, _>(0)?);
+        }
+        Ok(names)
+    }
+
+    /// Every installed hook, grouped by repo and hook type in install order
+    pub fn list_all_repo_hooks(&self) -> Result<Vec<RepoHook>, Box<dyn std::error::Error>> {
+        let query = "SELECT repo, name, type, disabled, disabled_until FROM repo_hooks ORDER BY repo, type, rowid";
+        let mut statement = self.connection.prepare(query)?;
+        let mut rows = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            rows.push(RepoHook {
+                repo: statement.read::<String, _>(0)?,
+                name: statement.read::<String, _>(1)?,
+                hook_type: statement.read::<String, _>(2)?,
+                disabled: statement.read::<i64, _>(3)? != 0,
+                disabled_until: statement.read::<Option<i64>, _>(4)?,
+            });
+        }
+        Ok(rows)
+    }
+
+    /// Every (repo, hook type) pair with at least one hook installed
+    pub fn list_installs(&self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        let query = "SELECT DISTINCT repo, type FROM repo_hooks ORDER BY repo, type";
+        let mut statement = self.connection.prepare(query)?;
+        let mut installs = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            installs.push((
+                statement.read::<String, _>(0)?,
+                statement.read::<String, _>(1)?,
+            ));
+        }
+        Ok(installs)
+    }
+
     pub fn check_if_hook_is_same(
         &self,
         repo: &str,
@@ -138,6 +362,33 @@ impl SqlLiteConfig {
         Ok(())
     }
 
+    /// Count a run skipped through `CRAB_HOOKS_SKIP`, `CRAB_HOOKS=0` or `crabhooks.skip`,
+    /// it is not part of `total_runs`
+    pub fn add_skipped_run(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "UPDATE hooks SET skipped_runs = skipped_runs + 1 WHERE name = ?";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, name))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    pub fn hook_counts(
+        &self,
+        name: &str,
+    ) -> Result<Option<HookCounts>, Box<dyn std::error::Error>> {
+        let query = "SELECT total_runs, succesful_runs, skipped_runs FROM hooks WHERE name = ?";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, name))?;
+        if let Ok(State::Row) = statement.next() {
+            return Ok(Some(HookCounts {
+                total_runs: statement.read::<i64, _>(0)?,
+                successful_runs: statement.read::<i64, _>(1)?,
+                skipped_runs: statement.read::<i64, _>(2)?,
+            }));
+        }
+        Ok(None)
+    }
+
     pub fn add_failed_run(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
         let query = "UPDATE hooks SET total_runs = total_runs + 1 WHERE name = ?";
         let mut statement = self.connection.prepare(query)?;
@@ -145,4 +396,511 @@ impl SqlLiteConfig {
         statement.next()?;
         Ok(())
     }
+
+    pub fn add_backup(
+        &self,
+        repo: &str,
+        hook_type: &HookTypes,
+        path: &str,
+        created_at: i64,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        let query = "INSERT INTO backups (repo, type, path, created_at) VALUES (?, ?, ?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, hook_type.to_string().as_str()))?;
+        statement.bind((3, path))?;
+        statement.bind((4, created_at))?;
+        statement.next()?;
+
+        let mut statement = self.connection.prepare("SELECT last_insert_rowid()")?;
+        statement.next()?;
+        Ok(statement.read::<i64, _>(0)?)
+    }
+
+    /// Backups for a repo and hook type, newest first
+    pub fn list_backups(
+        &self,
+        repo: &str,
+        hook_type: &HookTypes,
+    ) -> Result<Vec<Backup>, Box<dyn std::error::Error>> {
+        let query = "SELECT id, repo, type, path, created_at FROM backups WHERE repo = ? AND type = ? ORDER BY created_at DESC, id DESC";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, hook_type.to_string().as_str()))?;
+        let mut backups = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            backups.push(Backup {
+                id: statement.read::<i64, _>(0)?,
+                repo: statement.read::<String, _>(1)?,
+                hook_type: statement.read::<String, _>(2)?,
+                path: statement.read::<String, _>(3)?,
+                created_at: statement.read::<i64, _>(4)?,
+            });
+        }
+        Ok(backups)
+    }
+
+    /// Remember a repository found by `scan --register`
+    pub fn register_repo(
+        &self,
+        path: &str,
+        registered_at: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "INSERT OR IGNORE INTO repos (path, registered_at) VALUES (?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, path))?;
+        statement.bind((2, registered_at))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    pub fn list_registered_repos(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let query = "SELECT path FROM repos ORDER BY path";
+        let mut statement = self.connection.prepare(query)?;
+        let mut paths = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            paths.push(statement.read::<String, _>(0)?);
+        }
+        Ok(paths)
+    }
+
+    /// Mark an installed hook as owned by a profile
+    pub fn set_hook_profile(
+        &self,
+        repo: &str,
+        hook_type: &HookTypes,
+        name: &str,
+        profile: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "UPDATE repo_hooks SET profile = ? WHERE repo = ? AND type = ? AND name = ?";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, profile))?;
+        statement.bind((2, repo))?;
+        statement.bind((3, hook_type.to_string().as_str()))?;
+        statement.bind((4, name))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// The hooks a profile installed in a repo
+    pub fn list_profile_hooks(
+        &self,
+        repo: &str,
+        profile: &str,
+    ) -> Result<Vec<RepoHook>, Box<dyn std::error::Error>> {
+        let query =
+            "SELECT repo, name, type, disabled, disabled_until FROM repo_hooks WHERE repo = ? AND profile = ? ORDER BY rowid";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, profile))?;
+        let mut rows = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            rows.push(RepoHook {
+                repo: statement.read::<String, _>(0)?,
+                name: statement.read::<String, _>(1)?,
+                hook_type: statement.read::<String, _>(2)?,
+                disabled: statement.read::<i64, _>(3)? != 0,
+                disabled_until: statement.read::<Option<i64>, _>(4)?,
+            });
+        }
+        Ok(rows)
+    }
+
+    pub fn add_repo_profile(
+        &self,
+        repo: &str,
+        profile: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "INSERT OR IGNORE INTO repo_profiles (repo, profile) VALUES (?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, profile))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Repos the profile was applied to
+    pub fn list_profile_repos(
+        &self,
+        profile: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let query = "SELECT repo FROM repo_profiles WHERE profile = ? ORDER BY repo";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, profile))?;
+        let mut repos = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            repos.push(statement.read::<String, _>(0)?);
+        }
+        Ok(repos)
+    }
+
+    /// Every repo path mentioned anywhere in the database
+    pub fn list_known_repos(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let query = "SELECT repo FROM repo_hooks UNION SELECT path FROM repos UNION SELECT repo FROM repo_profiles UNION SELECT repo FROM repo_identities ORDER BY 1";
+        let mut statement = self.connection.prepare(query)?;
+        let mut repos = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            repos.push(statement.read::<String, _>(0)?);
+        }
+        Ok(repos)
+    }
+
+    /// Every name in `hooks`, including the ones no longer installed anywhere
+    pub fn list_hook_names(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let query = "SELECT name FROM hooks ORDER BY name";
+        let mut statement = self.connection.prepare(query)?;
+        let mut names = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            names.push(statement.read::<String, _>(0)?);
+        }
+        Ok(names)
+    }
+
+    /// Drop everything recorded about a repo, except its backups
+    pub fn forget_repo(&self, repo: &str) -> Result<(), Box<dyn std::error::Error>> {
+        for query in [
+            "DELETE FROM repo_hooks WHERE repo = ?",
+            "DELETE FROM repos WHERE path = ?",
+            "DELETE FROM repo_profiles WHERE repo = ?",
+            "DELETE FROM repo_identities WHERE repo = ?",
+            "DELETE FROM runs WHERE repo = ?",
+            "DELETE FROM result_cache WHERE repo = ?",
+        ] {
+            let mut statement = self.connection.prepare(query)?;
+            statement.bind((1, repo))?;
+            statement.next()?;
+        }
+        self.drop_orphan_logs()
+    }
+
+    /// Drop every hook installed in a repo as one hook type
+    pub fn forget_hook_type(
+        &self,
+        repo: &str,
+        hook_type: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "DELETE FROM repo_hooks WHERE repo = ? AND type = ?";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, hook_type))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Drop a hook, its run counts and its history
+    pub fn forget_hook(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        for query in [
+            "DELETE FROM hooks WHERE name = ?",
+            "DELETE FROM runs WHERE hook = ?",
+        ] {
+            let mut statement = self.connection.prepare(query)?;
+            statement.bind((1, name))?;
+            statement.next()?;
+        }
+        self.drop_orphan_logs()
+    }
+
+    /// Run `f` in a transaction, rolling everything back when it fails
+    pub fn transaction<F>(&self, f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce() -> Result<(), Box<dyn std::error::Error>>,
+    {
+        self.connection.execute("BEGIN")?;
+        match f() {
+            Ok(()) => {
+                self.connection.execute("COMMIT")?;
+                Ok(())
+            }
+            Err(e) => {
+                self.connection.execute("ROLLBACK")?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn set_repo_identity(
+        &self,
+        repo: &str,
+        identity: &RepoIdentity,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query =
+            "INSERT OR REPLACE INTO repo_identities (repo, root_commit, remote) VALUES (?, ?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, repo))?;
+        statement.bind((2, identity.root_commit.as_deref()))?;
+        statement.bind((3, identity.remote.as_deref()))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    pub fn list_repo_identities(
+        &self,
+    ) -> Result<Vec<(String, RepoIdentity)>, Box<dyn std::error::Error>> {
+        let query = "SELECT repo, root_commit, remote FROM repo_identities ORDER BY repo";
+        let mut statement = self.connection.prepare(query)?;
+        let mut identities = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            identities.push((
+                statement.read::<String, _>(0)?,
+                RepoIdentity {
+                    root_commit: statement.read::<Option<String>, _>(1)?,
+                    remote: statement.read::<Option<String>, _>(2)?,
+                },
+            ));
+        }
+        Ok(identities)
+    }
+
+    /// Point every row about `from` at `to`, rows `to` already has win
+    pub fn relocate_repo(&self, from: &str, to: &str) -> Result<(), Box<dyn std::error::Error>> {
+        for query in [
+            "DELETE FROM repo_hooks WHERE repo = ?2 AND EXISTS (SELECT 1 FROM repo_hooks AS r WHERE r.repo = ?1 AND r.name = repo_hooks.name AND r.type = repo_hooks.type)",
+            "UPDATE repo_hooks SET repo = ? WHERE repo = ?",
+            "UPDATE OR IGNORE repos SET path = ? WHERE path = ?",
+            "UPDATE OR IGNORE repo_profiles SET repo = ? WHERE repo = ?",
+            "UPDATE OR IGNORE repo_identities SET repo = ? WHERE repo = ?",
+            "UPDATE backups SET repo = ? WHERE repo = ?",
+            "UPDATE runs SET repo = ? WHERE repo = ?",
+            "UPDATE OR IGNORE result_cache SET repo = ? WHERE repo = ?",
+        ] {
+            let mut statement = self.connection.prepare(query)?;
+            statement.bind((1, to))?;
+            statement.bind((2, from))?;
+            statement.next()?;
+        }
+        self.forget_repo(from)
+    }
+
+    /// Move the installs, run counts, runs and cached results of a hook to a new key
+    pub fn rename_hook(&self, old: &str, new: &str) -> Result<(), Box<dyn std::error::Error>> {
+        for query in [
+            "UPDATE OR REPLACE hooks SET name = ? WHERE name = ?",
+            "UPDATE repo_hooks SET name = ? WHERE name = ?",
+            "UPDATE runs SET hook = ? WHERE hook = ?",
+            "UPDATE OR REPLACE result_cache SET hook = ? WHERE hook = ?",
+        ] {
+            let mut statement = self.connection.prepare(query)?;
+            statement.bind((1, new))?;
+            statement.bind((2, old))?;
+            statement.next()?;
+        }
+        Ok(())
+    }
+
+    /// Switch hooks of a repo off or back on, for one hook type or all of them.
+    /// Returns how many installs were changed.
+    pub fn set_hook_disabled(
+        &self,
+        repo: &str,
+        name: &str,
+        hook_type: Option<&HookTypes>,
+        disabled: bool,
+        until: Option<i64>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let query = "UPDATE repo_hooks SET disabled = ?, disabled_until = ? WHERE repo = ? AND name = ? AND (? IS NULL OR type = ?)";
+        let hook_type = hook_type.map(|t| t.to_string());
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, disabled as i64))?;
+        statement.bind((2, until))?;
+        statement.bind((3, repo))?;
+        statement.bind((4, name))?;
+        statement.bind((5, hook_type.as_deref()))?;
+        statement.bind((6, hook_type.as_deref()))?;
+        statement.next()?;
+        Ok(self.connection.change_count())
+    }
+
+    pub fn add_cached_pass(
+        &self,
+        hook: &str,
+        key: &CacheKey,
+        created_at: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "INSERT OR REPLACE INTO result_cache (repo, hook, config_hash, content_key, input, created_at) VALUES (?, ?, ?, ?, ?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, key.repo.as_str()))?;
+        statement.bind((2, hook))?;
+        statement.bind((3, key.config_hash.as_str()))?;
+        statement.bind((4, key.content_key.as_str()))?;
+        statement.bind((5, key.input.as_str()))?;
+        statement.bind((6, created_at))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    pub fn is_cached_pass(
+        &self,
+        hook: &str,
+        key: &CacheKey,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let query = "SELECT 1 FROM result_cache WHERE repo = ? AND hook = ? AND config_hash = ? AND content_key = ? AND input = ?";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, key.repo.as_str()))?;
+        statement.bind((2, hook))?;
+        statement.bind((3, key.config_hash.as_str()))?;
+        statement.bind((4, key.content_key.as_str()))?;
+        statement.bind((5, key.input.as_str()))?;
+        Ok(matches!(statement.next(), Ok(State::Row)))
+    }
+
+    /// Returns how many results were removed
+    pub fn clear_cached_passes(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        self.connection.execute("DELETE FROM result_cache")?;
+        Ok(self.connection.change_count())
+    }
+
+    /// Record a run, the `id` of `run` is ignored. Returns the id it was given.
+    pub fn add_run(&self, run: &RunRecord) -> Result<i64, Box<dyn std::error::Error>> {
+        let query = "INSERT INTO runs (hook, repo, type, head, started_at, duration_ms, exit_code, signal, outcome, config_hash, content_key, attempt) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, run.hook.as_str()))?;
+        statement.bind((2, run.repo.as_deref()))?;
+        statement.bind((3, run.hook_type.as_deref()))?;
+        statement.bind((4, run.head.as_deref()))?;
+        statement.bind((5, run.started_at))?;
+        statement.bind((6, run.duration_ms))?;
+        statement.bind((7, run.exit_code))?;
+        statement.bind((8, run.signal))?;
+        statement.bind((9, run.outcome.as_str()))?;
+        statement.bind((10, run.config_hash.as_deref()))?;
+        statement.bind((11, run.content_key.as_deref()))?;
+        statement.bind((12, run.attempt))?;
+        statement.next()?;
+
+        let mut statement = self.connection.prepare("SELECT last_insert_rowid()")?;
+        statement.next()?;
+        Ok(statement.read::<i64, _>(0)?)
+    }
+
+    /// Runs matching `filter`, newest first
+    pub fn list_runs(
+        &self,
+        filter: &RunFilter,
+    ) -> Result<Vec<RunRecord>, Box<dyn std::error::Error>> {
+        let query = format!(
+            "SELECT {} FROM runs
+            WHERE (?1 IS NULL OR hook = ?1) AND (?2 IS NULL OR repo = ?2) AND (?3 IS NULL OR type = ?3)
+            AND (?4 IS NULL OR outcome = ?4) AND (?5 IS NULL OR started_at >= ?5)
+            ORDER BY started_at DESC, id DESC LIMIT coalesce(?6, -1)",
+            RUN_COLUMNS
+        );
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, filter.hook.as_deref()))?;
+        statement.bind((2, filter.repo.as_deref()))?;
+        statement.bind((3, filter.hook_type.as_deref()))?;
+        statement.bind((4, filter.outcome.as_deref()))?;
+        statement.bind((5, filter.since))?;
+        statement.bind((6, filter.limit))?;
+        let mut runs = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            runs.push(Self::read_run(&statement)?);
+        }
+        Ok(runs)
+    }
+
+    /// A `runs` row selected with the columns of `RUN_COLUMNS`, in order
+    fn read_run(statement: &Statement) -> Result<RunRecord, Box<dyn std::error::Error>> {
+        Ok(RunRecord {
+            id: statement.read::<i64, _>(0)?,
+            hook: statement.read::<String, _>(1)?,
+            repo: statement.read::<Option<String>, _>(2)?,
+            hook_type: statement.read::<Option<String>, _>(3)?,
+            head: statement.read::<Option<String>, _>(4)?,
+            started_at: statement.read::<i64, _>(5)?,
+            duration_ms: statement.read::<i64, _>(6)?,
+            exit_code: statement.read::<Option<i64>, _>(7)?,
+            signal: statement.read::<Option<i64>, _>(8)?,
+            outcome: statement.read::<String, _>(9)?,
+            config_hash: statement.read::<Option<String>, _>(10)?,
+            content_key: statement.read::<Option<String>, _>(11)?,
+            attempt: statement.read::<i64, _>(12)?,
+        })
+    }
+
+    /// Whether a hook both passed and failed on the same config and content in the
+    /// same repo, in a run started at or after `since`
+    pub fn has_mixed_outcomes(
+        &self,
+        hook: &str,
+        since: i64,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let query = "SELECT 1 FROM runs WHERE hook = ? AND started_at >= ? AND content_key IS NOT NULL
+            AND outcome IN ('pass', 'fail', 'timeout')
+            GROUP BY repo, config_hash, content_key HAVING count(DISTINCT outcome = 'pass') = 2 LIMIT 1";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, hook))?;
+        statement.bind((2, since))?;
+        Ok(matches!(statement.next(), Ok(State::Row)))
+    }
+
+    /// Delete runs started before `before`, then all but the newest `keep` runs.
+    /// Returns how many runs were deleted.
+    pub fn trim_runs(&self, before: i64, keep: i64) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut deleted = 0;
+        for (query, value) in [
+            ("DELETE FROM runs WHERE started_at < ?", before),
+            (
+                "DELETE FROM runs WHERE id NOT IN (SELECT id FROM runs ORDER BY started_at DESC, id DESC LIMIT ?)",
+                keep,
+            ),
+        ] {
+            let mut statement = self.connection.prepare(query)?;
+            statement.bind((1, value))?;
+            statement.next()?;
+            deleted += self.connection.change_count();
+        }
+        self.drop_orphan_logs()?;
+        Ok(deleted)
+    }
+
+    /// Logs are deleted together with their runs
+    fn drop_orphan_logs(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection
+            .execute("DELETE FROM run_logs WHERE run_id NOT IN (SELECT id FROM runs)")?;
+        Ok(())
+    }
+
+    pub fn add_run_log(
+        &self,
+        run_id: i64,
+        output: &[u8],
+        size: i64,
+        truncated: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let query =
+            "INSERT OR REPLACE INTO run_logs (run_id, output, size, truncated) VALUES (?, ?, ?, ?)";
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, run_id))?;
+        statement.bind((2, output))?;
+        statement.bind((3, size))?;
+        statement.bind((4, truncated as i64))?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// The newest `limit` logs, of one hook or of all of them
+    pub fn list_run_logs(
+        &self,
+        hook: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<RunLog>, Box<dyn std::error::Error>> {
+        let query = format!(
+            "SELECT {}, output, size, truncated FROM runs JOIN run_logs ON run_logs.run_id = runs.id
+            WHERE (?1 IS NULL OR hook = ?1) ORDER BY started_at DESC, runs.id DESC LIMIT ?2",
+            RUN_COLUMNS
+        );
+        let mut statement = self.connection.prepare(query)?;
+        statement.bind((1, hook))?;
+        statement.bind((2, limit))?;
+        let mut logs = Vec::new();
+        while let Ok(State::Row) = statement.next() {
+            logs.push(RunLog {
+                run: Self::read_run(&statement)?,
+                output: statement.read::<Vec<u8>, _>(13)?,
+                size: statement.read::<i64, _>(14)?,
+                truncated: statement.read::<i64, _>(15)? != 0,
+            });
+        }
+        Ok(logs)
+    }
 }
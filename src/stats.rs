@@ -0,0 +1,294 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    history::{self, RunStatus},
+    sqllite::{RunFilter, RunRecord, SqlLiteConfig},
+    time,
+};
+
+/// How `stats` prints its report
+#[derive(Display, Clone, EnumString, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Counts and durations of a group of runs, of one hook or in one repo
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GroupStats {
+    /// The hook, or the repo, the runs belong to
+    pub name: String,
+    /// Runs that executed the command: passed, failed or timed out
+    pub runs: usize,
+    pub passed: usize,
+    /// Failed or timed out
+    pub failed: usize,
+    pub skipped: usize,
+    /// Share of the runs that passed, `None` without runs
+    pub success_rate: Option<f64>,
+    pub p50_ms: Option<i64>,
+    pub p95_ms: Option<i64>,
+    /// Inputs, a config and content in a repo, that both passed and failed
+    pub flaky_inputs: usize,
+    /// Share of the inputs run more than once that gave different outcomes,
+    /// `None` when no input was run twice
+    pub flakiness: Option<f64>,
+}
+
+impl GroupStats {
+    fn failure_rate(&self) -> f64 {
+        self.success_rate.map_or(0.0, |rate| 1.0 - rate)
+    }
+}
+
+/// The whole report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Stats {
+    /// Unix timestamp the window starts at
+    pub since: i64,
+    pub by_hook: Vec<GroupStats>,
+    pub by_repo: Vec<GroupStats>,
+    /// Hooks with the highest p95 duration
+    pub slowest: Vec<GroupStats>,
+    /// Hooks that failed, highest failure rate first
+    pub most_failing: Vec<GroupStats>,
+    /// Hooks with flaky inputs, flakiest first
+    pub flaky: Vec<GroupStats>,
+}
+
+/// Nearest-rank percentile of sorted durations
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn group_stats(name: &str, runs: &[&RunRecord]) -> GroupStats {
+    let mut stats = GroupStats {
+        name: name.to_string(),
+        ..GroupStats::default()
+    };
+    let mut durations = Vec::new();
+    // Whether each input passed, and whether it failed
+    let mut inputs: BTreeMap<_, (usize, bool, bool)> = BTreeMap::new();
+    for run in runs {
+        match run.outcome.parse::<RunStatus>() {
+            Ok(RunStatus::Pass) => stats.passed += 1,
+            Ok(RunStatus::Fail | RunStatus::Timeout) => stats.failed += 1,
+            Ok(RunStatus::Skip) | Err(_) => {
+                stats.skipped += 1;
+                continue;
+            }
+        }
+        durations.push(run.duration_ms);
+        if let Some(content) = &run.content_key {
+            let input = (&run.hook, &run.repo, &run.config_hash, content);
+            let (count, passed, failed) = inputs.entry(input).or_default();
+            *count += 1;
+            *passed |= run.outcome == RunStatus::Pass.to_string();
+            *failed |= run.outcome != RunStatus::Pass.to_string();
+        }
+    }
+    let repeated = inputs.values().filter(|(count, _, _)| *count > 1).count();
+    stats.flaky_inputs = inputs.values().filter(|(_, p, f)| *p && *f).count();
+    if repeated > 0 {
+        stats.flakiness = Some(stats.flaky_inputs as f64 / repeated as f64);
+    }
+    stats.runs = stats.passed + stats.failed;
+    if stats.runs > 0 {
+        stats.success_rate = Some(stats.passed as f64 / stats.runs as f64);
+    }
+    durations.sort_unstable();
+    stats.p50_ms = percentile(&durations, 0.5);
+    stats.p95_ms = percentile(&durations, 0.95);
+    stats
+}
+
+fn group_by<'a>(runs: &'a [RunRecord], key: impl Fn(&'a RunRecord) -> &'a str) -> Vec<GroupStats> {
+    let mut groups: BTreeMap<&str, Vec<&RunRecord>> = BTreeMap::new();
+    for run in runs {
+        groups.entry(key(run)).or_default().push(run);
+    }
+    groups
+        .iter()
+        .map(|(name, runs)| group_stats(name, runs))
+        .collect()
+}
+
+/// Compute the report from the runs in the window, `top` entries in the rankings
+pub fn compute(runs: &[RunRecord], since: i64, top: usize) -> Stats {
+    let by_hook = group_by(runs, |r| r.hook.as_str());
+    let by_repo = group_by(runs, |r| r.repo.as_deref().unwrap_or("-"));
+
+    let mut slowest: Vec<GroupStats> = by_hook.iter().filter(|s| s.runs > 0).cloned().collect();
+    slowest.sort_by_key(|s| std::cmp::Reverse(s.p95_ms));
+    slowest.truncate(top);
+
+    let mut most_failing: Vec<GroupStats> =
+        by_hook.iter().filter(|s| s.failed > 0).cloned().collect();
+    most_failing.sort_by(|a, b| {
+        b.failure_rate()
+            .total_cmp(&a.failure_rate())
+            .then(b.failed.cmp(&a.failed))
+    });
+    most_failing.truncate(top);
+
+    let mut flaky: Vec<GroupStats> = by_hook
+        .iter()
+        .filter(|s| s.flaky_inputs > 0)
+        .cloned()
+        .collect();
+    flaky.sort_by(|a, b| {
+        b.flakiness
+            .unwrap_or(0.0)
+            .total_cmp(&a.flakiness.unwrap_or(0.0))
+            .then(b.flaky_inputs.cmp(&a.flaky_inputs))
+    });
+    flaky.truncate(top);
+
+    Stats {
+        since,
+        by_hook,
+        by_repo,
+        slowest,
+        most_failing,
+        flaky,
+    }
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+    rate.map_or("-".to_string(), |r| format!("{:.0}%", r * 100.0))
+}
+
+fn format_ms(ms: Option<i64>) -> String {
+    ms.map_or("-".to_string(), history::format_duration)
+}
+
+fn print_table(title: &str, groups: &[GroupStats]) {
+    let width = groups
+        .iter()
+        .map(|g| g.name.len())
+        .chain([title.len()])
+        .max()
+        .unwrap_or(0);
+    println!(
+        "{:<width$}  {:>5}  {:>6}  {:>6}  {:>7}  {:>7}  {:>7}  {:>7}  {:>5}",
+        title, "Runs", "Passed", "Failed", "Skipped", "Success", "p50", "p95", "Flaky"
+    );
+    for g in groups {
+        println!(
+            "{:<width$}  {:>5}  {:>6}  {:>6}  {:>7}  {:>7}  {:>7}  {:>7}  {:>5}",
+            g.name,
+            g.runs,
+            g.passed,
+            g.failed,
+            g.skipped,
+            format_rate(g.success_rate),
+            format_ms(g.p50_ms),
+            format_ms(g.p95_ms),
+            format_rate(g.flakiness),
+        );
+    }
+}
+
+/// Quote a CSV field when it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// The per hook and per repo rows as CSV, `group` telling them apart
+pub fn to_csv(stats: &Stats) -> String {
+    let mut csv = String::from(
+        "group,name,runs,passed,failed,skipped,success_rate,p50_ms,p95_ms,flaky_inputs,flakiness\n",
+    );
+    let rows = stats
+        .by_hook
+        .iter()
+        .map(|g| ("hook", g))
+        .chain(stats.by_repo.iter().map(|g| ("repo", g)));
+    for (group, g) in rows {
+        let optional = |v: Option<String>| v.unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            group,
+            csv_field(&g.name),
+            g.runs,
+            g.passed,
+            g.failed,
+            g.skipped,
+            optional(g.success_rate.map(|r| format!("{:.4}", r))),
+            optional(g.p50_ms.map(|ms| ms.to_string())),
+            optional(g.p95_ms.map(|ms| ms.to_string())),
+            g.flaky_inputs,
+            optional(g.flakiness.map(|r| format!("{:.4}", r))),
+        ));
+    }
+    csv
+}
+
+/// Report success rates and durations of the runs since `since`
+pub fn stats(
+    since: i64,
+    top: usize,
+    format: &OutputFormat,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let runs = sql_config.list_runs(&RunFilter {
+        since: Some(since),
+        ..RunFilter::default()
+    })?;
+    let stats = compute(&runs, since, top);
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+        OutputFormat::Csv => print!("{}", to_csv(&stats)),
+        OutputFormat::Table => {
+            if runs.is_empty() {
+                println!("No runs since {}", time::format_time(since));
+                return Ok(());
+            }
+            println!("Runs since {}\n", time::format_time(since));
+            print_table("Hook", &stats.by_hook);
+            println!();
+            print_table("Repo", &stats.by_repo);
+            println!("\nSlowest (p95):");
+            for g in &stats.slowest {
+                println!("  {}  {}", g.name, format_ms(g.p95_ms));
+            }
+            if !stats.most_failing.is_empty() {
+                println!("\nMost failing:");
+                for g in &stats.most_failing {
+                    println!(
+                        "  {}  {} of {} failed ({})",
+                        g.name,
+                        g.failed,
+                        g.runs,
+                        format_rate(Some(g.failure_rate()))
+                    );
+                }
+            }
+            if !stats.flaky.is_empty() {
+                println!("\nFlaky (same content, different outcomes):");
+                for g in &stats.flaky {
+                    println!(
+                        "  {}  {} input(s), {}",
+                        g.name,
+                        g.flaky_inputs,
+                        format_rate(g.flakiness)
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use git2::Repository;
+use globset::{Glob, GlobBuilder};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    bulk,
+    git_hook::{self, ApplyOptions},
+    hook_types::HookTypes,
+    repos, scan, shim,
+    sqllite::SqlLiteConfig,
+    yml_parser::{Config, RepoRule},
+};
+
+/// One change needed to bring a repo in line with the config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Install {
+        hook_type: String,
+        name: String,
+    },
+    Remove {
+        hook_type: String,
+        name: String,
+    },
+    /// The hooks are recorded, but the hook file is missing or was replaced
+    RewriteShim {
+        hook_type: String,
+    },
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Install { hook_type, name } => write!(f, "+ {}: {}", hook_type, name),
+            Action::Remove { hook_type, name } => write!(f, "- {}: {}", hook_type, name),
+            Action::RewriteShim { hook_type } => {
+                write!(f, "~ {}: rewrite hook script", hook_type)
+            }
+        }
+    }
+}
+
+/// The changes for one repo
+#[derive(Debug)]
+pub struct RepoPlan {
+    pub repo: PathBuf,
+    pub actions: Vec<Action>,
+}
+
+fn rule_matches(rule: &RepoRule, repo: &Path) -> bool {
+    if let Some(pattern) = &rule.path {
+        let pattern = scan::expand_home(pattern);
+        let matched = GlobBuilder::new(&pattern.to_string_lossy())
+            .literal_separator(true)
+            .build()
+            .is_ok_and(|g| g.compile_matcher().is_match(repo));
+        if matched {
+            return true;
+        }
+    }
+    if let Some(pattern) = &rule.remote {
+        let Ok(matcher) = Glob::new(pattern).map(|g| g.compile_matcher()) else {
+            return false;
+        };
+        let Ok(git_repo) = Repository::open(repo) else {
+            return false;
+        };
+        let remotes = git_repo.remotes().ok();
+        return remotes.iter().flat_map(|r| r.iter().flatten()).any(|name| {
+            git_repo
+                .find_remote(name)
+                .ok()
+                .and_then(|r| r.url().map(|u| matcher.is_match(u)))
+                .unwrap_or(false)
+        });
+    }
+    false
+}
+
+/// The (hook type, hook name) pairs the config declares for a repo, in declaration
+/// order, or `None` when no rule matches it
+pub fn desired_hooks(config: &Config, repo: &Path) -> Option<Vec<(String, String)>> {
+    let mut matched = false;
+    let mut wanted: Vec<(String, String)> = Vec::new();
+    for rule in config.repos.iter().filter(|r| rule_matches(r, repo)) {
+        matched = true;
+        let sets = rule
+            .profiles
+            .iter()
+            .filter_map(|p| config.profiles.get(p))
+            .chain(std::iter::once(&rule.hooks));
+        for set in sets {
+            for (hook_type, names) in set {
+                for name in names {
+                    let pair = (hook_type.clone(), name.clone());
+                    if !wanted.contains(&pair) {
+                        wanted.push(pair);
+                    }
+                }
+            }
+        }
+    }
+    matched.then_some(wanted)
+}
+
+/// Compare the declared state with `repo_hooks` and the hook files.
+///
+/// Only repos known to crab-hooks (registered by `scan --register`, or with
+/// hooks installed) and matched by a rule are considered; only repos that need
+/// changes are returned.
+pub fn plan(
+    config: &Config,
+    sql_config: &SqlLiteConfig,
+) -> Result<Vec<RepoPlan>, Box<dyn std::error::Error>> {
+    let candidates = bulk::resolve_repos(&[], None, true, sql_config)?;
+    let mut plans = Vec::new();
+    for repo in candidates {
+        if !repo.join(".git").exists() {
+            continue;
+        }
+        let Some(wanted) = desired_hooks(config, &repo) else {
+            continue;
+        };
+        let repo_str = repo.to_string_lossy();
+        let installed: Vec<(String, String)> = sql_config
+            .list_all_repo_hooks()?
+            .into_iter()
+            .filter(|r| r.repo == repo_str)
+            .map(|r| (r.hook_type, r.name))
+            .collect();
+
+        // Installed rows hold database keys, the config refers to hooks by name
+        let wanted_keys: Vec<(String, String)> = wanted
+            .iter()
+            .map(|(t, name)| (t.clone(), config.key_of(name).to_string()))
+            .collect();
+
+        let mut actions = Vec::new();
+        for (hook_type, name) in &installed {
+            if !wanted_keys.contains(&(hook_type.clone(), name.clone())) {
+                actions.push(Action::Remove {
+                    hook_type: hook_type.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+        for ((hook_type, name), key) in wanted.iter().zip(&wanted_keys) {
+            if !installed.contains(key) {
+                actions.push(Action::Install {
+                    hook_type: hook_type.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+
+        // Installing or removing rewrites the script anyway, so only check untouched types
+        let kept_types: BTreeSet<&String> = installed
+            .iter()
+            .filter(|pair| wanted_keys.contains(pair))
+            .map(|(hook_type, _)| hook_type)
+            .collect();
+        for hook_type in kept_types {
+            let touched = actions.iter().any(|a| match a {
+                Action::Install { hook_type: t, .. } | Action::Remove { hook_type: t, .. } => {
+                    t == hook_type
+                }
+                Action::RewriteShim { .. } => false,
+            });
+            if !touched && !repos::script_is_managed(&repo, hook_type) {
+                actions.push(Action::RewriteShim {
+                    hook_type: hook_type.clone(),
+                });
+            }
+        }
+
+        if !actions.is_empty() {
+            plans.push(RepoPlan { repo, actions });
+        }
+    }
+    Ok(plans)
+}
+
+fn print_plan(plans: &[RepoPlan]) {
+    for plan in plans {
+        println!("{}", plan.repo.display());
+        for action in &plan.actions {
+            println!("  {}", action);
+        }
+    }
+}
+
+fn apply_plan(
+    plan: &RepoPlan,
+    config: &Config,
+    options: &ApplyOptions,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for action in &plan.actions {
+        match action {
+            Action::Remove { hook_type, name } => {
+                let hook_type = parse_hook_type(hook_type)?;
+                git_hook::uninstall_hook(name, &plan.repo, &hook_type, &options.shim, sql_config)?;
+            }
+            Action::Install { hook_type, name } => {
+                let hook_type = parse_hook_type(hook_type)?;
+                let hook = config
+                    .find_hook(name)
+                    .ok_or_else(|| format!("No hook named {} in the config", name))?;
+                hook.apply_hook(&plan.repo, &hook_type, options, sql_config)?;
+            }
+            Action::RewriteShim { hook_type } => {
+                let hook_type = parse_hook_type(hook_type)?;
+                let file_path = shim::hook_path(&plan.repo, &hook_type);
+                let is_unmanaged_script = file_path.exists()
+                    && !std::fs::read_to_string(&file_path).is_ok_and(|c| shim::is_shim(&c));
+                if is_unmanaged_script && !options.force {
+                    return Err(format!(
+                        "{} was replaced by an unmanaged script, use --force to overwrite it",
+                        file_path.display()
+                    )
+                    .into());
+                }
+                shim::write_shim(&plan.repo, &hook_type, &options.shim, sql_config)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_hook_type(hook_type: &str) -> Result<HookTypes, Box<dyn std::error::Error>> {
+    HookTypes::from_str(hook_type).map_err(|_| format!("Unknown hook type {}", hook_type).into())
+}
+
+/// Print the changes needed to match the config, and apply them unless `dry_run`
+pub fn sync(
+    config: &Config,
+    options: &ApplyOptions,
+    dry_run: bool,
+    continue_on_error: bool,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let plans = plan(config, sql_config)?;
+    if plans.is_empty() {
+        println!("Everything is in sync");
+        return Ok(());
+    }
+    print_plan(&plans);
+    if dry_run {
+        return Ok(());
+    }
+
+    let repos: Vec<PathBuf> = plans.iter().map(|p| p.repo.clone()).collect();
+    bulk::for_each_repo(&repos, continue_on_error, |repo| {
+        let plan = plans
+            .iter()
+            .find(|p| p.repo == repo)
+            .expect("plan exists for every repo");
+        apply_plan(plan, config, options, sql_config)
+    })
+}
+
+/// Report drift from the config without changing anything, fails when there is any
+pub fn status(
+    config: &Config,
+    sql_config: &SqlLiteConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let plans = plan(config, sql_config)?;
+    if plans.is_empty() {
+        println!("Everything is in sync");
+        return Ok(());
+    }
+    print_plan(&plans);
+    Err(format!(
+        "{} repositories drifted from the config, run `crab-hooks sync` to fix them",
+        plans.len()
+    )
+    .into())
+}
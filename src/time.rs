@@ -0,0 +1,117 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the unix epoch
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Parse the value of `--until`: a duration from `now` like `30m`, `12h`, `1d` or `2w`,
+/// or a date `YYYY-MM-DD` (midnight UTC) or `YYYY-MM-DDTHH:MM` (UTC)
+pub fn parse_until(value: &str, now: i64) -> Result<i64, String> {
+    match parse_duration(value) {
+        Some(seconds) => Ok(now + seconds),
+        None => parse_date(value),
+    }
+}
+
+/// Parse the value of `--since`: like `parse_until`, but a duration goes back from `now`
+pub fn parse_since(value: &str, now: i64) -> Result<i64, String> {
+    match parse_duration(value) {
+        Some(seconds) => Ok(now - seconds),
+        None => parse_date(value),
+    }
+}
+
+/// Seconds in a duration like `30m`, `12h`, `1d` or `2w`
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let unit = match value.chars().last()? {
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => return None,
+    };
+    value[..value.len() - 1]
+        .parse::<i64>()
+        .ok()
+        .map(|n| n * unit)
+}
+
+/// Parse a date `YYYY-MM-DD` (midnight UTC) or `YYYY-MM-DDTHH:MM` (UTC)
+fn parse_date(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let invalid = || {
+        format!(
+            "invalid time `{}`, expected e.g. `1d`, `12h`, `2026-05-01` or `2026-05-01T18:00`",
+            value
+        )
+    };
+    let (date, time) = value.split_once('T').unwrap_or((value, "00:00"));
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|p| p.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let time: Vec<i64> = time
+        .split(':')
+        .map(|p| p.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    let (&[year, month, day], &[hour, minute]) = (date.as_slice(), time.as_slice()) else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..=23).contains(&hour)
+        || !(0..=59).contains(&minute)
+    {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60)
+}
+
+/// Number of days in a month of the Gregorian calendar
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Format a unix timestamp as `YYYY-MM-DD HH:MM UTC`
+pub fn format_time(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400);
+    let secs = timestamp.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
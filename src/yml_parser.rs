@@ -1,8 +1,10 @@
 use glob::Pattern;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fmt,
-    fs::File,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use which::which;
 
@@ -15,6 +17,10 @@ pub struct ValidationError {
 
 impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hook_name.is_empty() {
+            // Settings that do not belong to a hook
+            return write!(f, "field `{}` — {}", self.field, self.problem);
+        }
         write!(
             f,
             "hook `{}`: field `{}` — {}",
@@ -23,119 +29,496 @@ impl fmt::Display for ValidationError {
     }
 }
 
-use crate::git_hook::GitHook;
+use crate::{git_hook::GitHook, hook_types::HookTypes, shim};
 
-pub fn read_file(config_file: PathBuf) -> Result<Vec<GitHook>, Box<dyn std::error::Error>> {
-    let f: File;
-    if Path::new(&config_file).exists() {
-        f = std::fs::File::open(config_file)?;
+/// The whole config file.
+///
+/// It is either a plain list of hooks, or a mapping with the hooks under `hooks:`
+/// next to the settings below.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub hooks: Vec<GitHook>,
+    /// Template for the installed hook scripts, see `shim::render_shim`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shim_template: Option<String>,
+    /// Same as `shim_template`, but read from a file relative to the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shim_template_file: Option<PathBuf>,
+    /// Named sets of hooks, mapping hook types to hook names
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Desired state: which hooks belong in which repos, used by `sync` and `status`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repos: Vec<RepoRule>,
+    /// How long the run history is kept, see `HistoryRetention`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryRetention>,
+    /// Folder of the config file, used to resolve relative paths
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+/// Hook type (e.g. `pre-push`) to the names of the hooks installed as that type
+pub type Profile = BTreeMap<String, Vec<String>>;
+
+/// Hooks declared for every repo matching `path` or `remote`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoRule {
+    /// Glob on the repo path, a leading `~` is the home directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Glob on any of the repo's remote URLs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: Profile,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+}
+
+/// Limits on the run history, older runs are deleted after each run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRetention {
+    /// Runs older than this many days are deleted
+    #[serde(default = "HistoryRetention::default_keep_days")]
+    pub keep_days: i64,
+    /// At most this many runs are kept, the newest ones
+    #[serde(default = "HistoryRetention::default_max_runs")]
+    pub max_runs: i64,
+}
+
+impl HistoryRetention {
+    fn default_keep_days() -> i64 {
+        90
+    }
+
+    fn default_max_runs() -> i64 {
+        10_000
+    }
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        HistoryRetention {
+            keep_days: Self::default_keep_days(),
+            max_runs: Self::default_max_runs(),
+        }
+    }
+}
+
+impl Config {
+    /// Whether the config only holds hooks, so it can be written as a plain list
+    fn is_plain(&self) -> bool {
+        self.shim_template.is_none()
+            && self.shim_template_file.is_none()
+            && self.profiles.is_empty()
+            && self.repos.is_empty()
+            && self.history.is_none()
+    }
+
+    pub fn find_hook(&self, name: &str) -> Option<&GitHook> {
+        self.hooks.iter().find(|h| h.name == name)
+    }
+
+    /// The hook stored in the database as `key`, see `GitHook::key`
+    pub fn find_hook_by_key(&self, key: &str) -> Option<&GitHook> {
+        self.hooks.iter().find(|h| h.key() == key)
+    }
+
+    /// The database key of a hook name, the name itself when it is not in the config
+    pub fn key_of<'a>(&'a self, name: &'a str) -> &'a str {
+        self.find_hook(name).map(GitHook::key).unwrap_or(name)
+    }
+
+    /// The configured hook script template, if any
+    pub fn load_shim_template(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(template) = &self.shim_template {
+            return Ok(Some(template.clone()));
+        }
+        match &self.shim_template_file {
+            Some(file) => {
+                let path = self.base_dir.join(file);
+                let template = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Cannot read shim template {:?}: {}", path, e))?;
+                Ok(Some(template))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn locate_file(config_file: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if config_file.exists() {
+        Ok(config_file.to_path_buf())
     } else if Path::new("./config.yml").exists() {
-        f = std::fs::File::open("./config.yml")?;
+        Ok(PathBuf::from("./config.yml"))
     } else {
-        return Err(format!(
+        Err(format!(
             "Cannot locate a config.yml file, please make one here: {:?}",
             config_file
         )
-        .into());
+        .into())
     }
+}
+
+pub fn read_config(config_file: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+    let path = locate_file(config_file)?;
+    let text = std::fs::read_to_string(&path)?;
+    // Look at the root first and read the matching shape, so a mistake is
+    // reported with its own message and location
+    let mut config = match serde_yaml::from_str(&text)? {
+        serde_yaml::Value::Sequence(_) => Config {
+            hooks: serde_yaml::from_str(&text)?,
+            ..Config::default()
+        },
+        _ => serde_yaml::from_str(&text)?,
+    };
+    config.base_dir = path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    Ok(config)
+}
 
-    let hooks: Vec<GitHook> = serde_yaml::from_reader(f)?;
-    Ok(hooks)
+pub fn read_file(config_file: PathBuf) -> Result<Vec<GitHook>, Box<dyn std::error::Error>> {
+    Ok(read_config(&config_file)?.hooks)
+}
+
+pub fn write_config(config_file: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let yaml_str = if config.is_plain() {
+        serde_yaml::to_string(&config.hooks)?
+    } else {
+        serde_yaml::to_string(config)?
+    };
+    std::fs::write(config_file, yaml_str)?;
+    Ok(())
+}
+
+/// Exit code used when the config fails validation (`EX_CONFIG` from sysexits.h)
+pub const CONFIG_ERROR_EXIT_CODE: i32 = 78;
+
+/// Returned when the config cannot be read, or one or more hooks fail validation
+#[derive(Debug)]
+pub enum ConfigError {
+    Unreadable(String),
+    Invalid(Vec<ValidationError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable(e) => write!(f, "Config error: {}", e),
+            ConfigError::Invalid(errors) => {
+                write!(f, "Config error: {} problem(s) found", errors.len())
+            }
+        }
+    }
 }
 
+impl std::error::Error for ConfigError {}
+
 pub fn test_config(config_file: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    let hooks = read_file(config_file)?;
+    let config = read_config(&config_file).map_err(|e| ConfigError::Unreadable(e.to_string()))?;
+    let mut errors = validate_settings(&config);
+    errors.extend(config.hooks.iter().flat_map(validate_hook));
+    report(errors)
+}
 
-    let mut errors = Vec::new();
+/// Validate the shim template and only the named hooks, so one broken entry, a
+/// hook, profile or repo rule, does not block the others
+pub fn test_hooks(config_file: PathBuf, names: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let config = read_config(&config_file).map_err(|e| ConfigError::Unreadable(e.to_string()))?;
+    report(validate_named_hooks(&config, names))
+}
 
-    for hook in hooks {
-        // --- name
-        if hook.name.trim().is_empty() {
+/// Validate a profile and its hooks, like `test_hooks`
+pub fn test_profile(config_file: PathBuf, profile: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = read_config(&config_file).map_err(|e| ConfigError::Unreadable(e.to_string()))?;
+    let Some(hook_types) = config.profiles.get(profile) else {
+        return report(validate_named_hooks(&config, &[]));
+    };
+    let names: Vec<&str> = hook_types.values().flatten().map(String::as_str).collect();
+    let mut errors = validate_named_hooks(&config, &names);
+    validate_hook_set(
+        &config,
+        &format!("profiles.{}", profile),
+        hook_types,
+        &mut errors,
+    );
+    report(errors)
+}
+
+fn validate_named_hooks(config: &Config, names: &[&str]) -> Vec<ValidationError> {
+    let mut errors = validate_shim_template(config);
+    for hook in &config.hooks {
+        if !names.contains(&hook.name.as_str()) && !names.contains(&hook.key()) {
+            continue;
+        }
+        errors.extend(validate_hook(hook));
+        if config
+            .hooks
+            .iter()
+            .filter(|h| h.key() == hook.key())
+            .count()
+            > 1
+        {
             errors.push(ValidationError {
                 hook_name: hook.name.clone(),
-                field: "name".into(),
-                problem: "must not be empty".into(),
+                field: "id".into(),
+                problem: format!("`{}` is also the id or name of another hook", hook.key()),
             });
         }
+    }
+    errors
+}
+
+fn report(errors: Vec<ValidationError>) -> Result<(), Box<dyn std::error::Error>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        for e in &errors {
+            eprintln!("Config error: {}", e);
+        }
+        Err(Box::new(ConfigError::Invalid(errors)))
+    }
+}
+
+fn validate_shim_template(config: &Config) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    if config.shim_template.is_some() && config.shim_template_file.is_some() {
+        errors.push(ValidationError {
+            hook_name: String::new(),
+            field: "shim_template".into(),
+            problem: "only one of `shim_template` and `shim_template_file` may be set".into(),
+        });
+    }
+    match config.load_shim_template() {
+        Ok(Some(template)) => {
+            if let Err(problem) = shim::validate_template(&template) {
+                errors.push(ValidationError {
+                    hook_name: String::new(),
+                    field: "shim_template".into(),
+                    problem,
+                });
+            }
+        }
+        Ok(None) => (),
+        Err(e) => errors.push(ValidationError {
+            hook_name: String::new(),
+            field: "shim_template_file".into(),
+            problem: e.to_string(),
+        }),
+    }
+    errors
+}
+
+/// Validate the settings that are not part of a hook: the shim template, hook ids,
+/// run history, profiles and repo rules
+pub fn validate_settings(config: &Config) -> Vec<ValidationError> {
+    let mut errors = validate_shim_template(config);
 
-        // --- command.cmd must be non‐empty
-        let cmd = hook.command.cmd.trim();
-        if cmd.is_empty() {
+    // --- hook ids, every hook needs its own database key
+    for (i, hook) in config.hooks.iter().enumerate() {
+        if hook.id.as_deref().is_some_and(|id| id.trim().is_empty()) {
             errors.push(ValidationError {
                 hook_name: hook.name.clone(),
-                field: "command.cmd".into(),
+                field: "id".into(),
                 problem: "must not be empty".into(),
             });
-        } else {
-            // --- check existence without executing
-            if let Some(dir) = &hook.command.directory {
-                // Build the path to <directory>/<cmd>
-                let candidate = dir.join(cmd);
-
-                // Try finding it globally on PATH as well
-                let found_globally = which(cmd).is_ok();
-
-                if !candidate.exists() && !found_globally {
-                    errors.push(ValidationError {
-                        hook_name: hook.name.clone(),
-                        field: "command.cmd".into(),
-                        problem: format!(
-                            "could not find executable `{}` in directory {:?} or on PATH",
-                            cmd, dir
-                        ),
-                    });
-                }
-            } else {
-                // No directory specified → must find it globally
-                if which(cmd).is_err() {
-                    errors.push(ValidationError {
-                        hook_name: hook.name.clone(),
-                        field: "command.cmd".into(),
-                        problem: format!("could not locate `{}` on PATH", cmd),
-                    });
-                }
+        }
+        if config.hooks[..i].iter().any(|h| h.key() == hook.key()) {
+            errors.push(ValidationError {
+                hook_name: hook.name.clone(),
+                field: "id".into(),
+                problem: format!("`{}` is already the id or name of another hook", hook.key()),
+            });
+        }
+    }
+
+    // --- run history
+    if let Some(history) = &config.history {
+        for (field, value) in [
+            ("history.keep_days", history.keep_days),
+            ("history.max_runs", history.max_runs),
+        ] {
+            if value < 1 {
+                errors.push(ValidationError {
+                    hook_name: String::new(),
+                    field: field.into(),
+                    problem: "must be at least 1".into(),
+                });
+            }
+        }
+    }
+
+    // --- profiles
+    for (profile, hook_types) in &config.profiles {
+        validate_hook_set(
+            config,
+            &format!("profiles.{}", profile),
+            hook_types,
+            &mut errors,
+        );
+    }
+
+    // --- desired state
+    for (i, rule) in config.repos.iter().enumerate() {
+        let field = format!("repos[{}]", i);
+        if rule.path.is_none() && rule.remote.is_none() {
+            errors.push(ValidationError {
+                hook_name: String::new(),
+                field: field.clone(),
+                problem: "needs a `path` or `remote` pattern".into(),
+            });
+        }
+        for pat in rule.path.iter().chain(rule.remote.iter()) {
+            if let Err(e) = globset::Glob::new(pat) {
+                errors.push(ValidationError {
+                    hook_name: String::new(),
+                    field: field.clone(),
+                    problem: format!("invalid glob `{}`: {}", pat, e),
+                });
+            }
+        }
+        for profile in &rule.profiles {
+            if !config.profiles.contains_key(profile) {
+                errors.push(ValidationError {
+                    hook_name: String::new(),
+                    field: format!("{}.profiles", field),
+                    problem: format!("no profile named `{}` in the config", profile),
+                });
+            }
+        }
+        validate_hook_set(
+            config,
+            &format!("{}.hooks", field),
+            &rule.hooks,
+            &mut errors,
+        );
+    }
+
+    errors
+}
+
+/// Check a hook type to hook names mapping, as used by profiles and repo rules
+fn validate_hook_set(
+    config: &Config,
+    field: &str,
+    hook_types: &Profile,
+    errors: &mut Vec<ValidationError>,
+) {
+    for (hook_type, names) in hook_types {
+        if HookTypes::from_str(hook_type).is_err() {
+            errors.push(ValidationError {
+                hook_name: String::new(),
+                field: field.to_string(),
+                problem: format!("unknown hook type `{}`", hook_type),
+            });
+        }
+        for name in names {
+            if config.find_hook(name).is_none() {
+                errors.push(ValidationError {
+                    hook_name: String::new(),
+                    field: format!("{}.{}", field, hook_type),
+                    problem: format!("no hook named `{}` in the config", name),
+                });
             }
         }
+    }
+}
+
+pub fn validate_hook(hook: &GitHook) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    // --- name
+    if hook.name.trim().is_empty() {
+        errors.push(ValidationError {
+            hook_name: hook.name.clone(),
+            field: "name".into(),
+            problem: "must not be empty".into(),
+        });
+    }
 
-        // --- directory
+    // --- command.cmd must be non‐empty
+    let cmd = hook.command.cmd.trim();
+    if cmd.is_empty() {
+        errors.push(ValidationError {
+            hook_name: hook.name.clone(),
+            field: "command.cmd".into(),
+            problem: "must not be empty".into(),
+        });
+    } else {
+        // --- check existence without executing
         if let Some(dir) = &hook.command.directory {
-            if dir.as_os_str().is_empty() {
+            // Build the path to <directory>/<cmd>
+            let candidate = dir.join(cmd);
+
+            // Try finding it globally on PATH as well
+            let found_globally = which(cmd).is_ok();
+
+            if !candidate.exists() && !found_globally {
                 errors.push(ValidationError {
                     hook_name: hook.name.clone(),
-                    field: "command.directory".into(),
-                    problem: "path is empty".into(),
+                    field: "command.cmd".into(),
+                    problem: format!(
+                        "could not find executable `{}` in directory {:?} or on PATH",
+                        cmd, dir
+                    ),
+                });
+            }
+        } else {
+            // No directory specified → must find it globally
+            if which(cmd).is_err() {
+                errors.push(ValidationError {
+                    hook_name: hook.name.clone(),
+                    field: "command.cmd".into(),
+                    problem: format!("could not locate `{}` on PATH", cmd),
                 });
             }
         }
+    }
 
-        // --- glob patterns
-        if hook.glob_pattern.is_empty() {
+    // --- directory
+    if let Some(dir) = &hook.command.directory {
+        if dir.as_os_str().is_empty() {
             errors.push(ValidationError {
                 hook_name: hook.name.clone(),
-                field: "glob_pattern".into(),
-                problem: "must contain at least one pattern".into(),
+                field: "command.directory".into(),
+                problem: "path is empty".into(),
             });
-        } else {
-            for pat in &hook.glob_pattern {
-                if let Err(e) = Pattern::new(pat) {
-                    errors.push(ValidationError {
-                        hook_name: hook.name.clone(),
-                        field: "glob_pattern".into(),
-                        problem: format!("invalid glob `{}`: {}", pat, e),
-                    });
-                }
-            }
         }
     }
 
-    if errors.is_empty() {
-        Ok(())
+    // --- timeout
+    if hook.timeout == Some(0) {
+        errors.push(ValidationError {
+            hook_name: hook.name.clone(),
+            field: "timeout".into(),
+            problem: "must be at least 1 second".into(),
+        });
+    }
+
+    // --- glob patterns
+    if hook.glob_pattern.is_empty() {
+        errors.push(ValidationError {
+            hook_name: hook.name.clone(),
+            field: "glob_pattern".into(),
+            problem: "must contain at least one pattern".into(),
+        });
     } else {
-        for e in errors {
-            eprintln!("Config error: {}", e);
+        for pat in &hook.glob_pattern {
+            if let Err(e) = Pattern::new(pat) {
+                errors.push(ValidationError {
+                    hook_name: hook.name.clone(),
+                    field: "glob_pattern".into(),
+                    problem: format!("invalid glob `{}`: {}", pat, e),
+                });
+            }
         }
-        Err("Config error".into())
     }
+
+    errors
 }
 
 pub fn display_hooks(config_file: PathBuf) {
@@ -0,0 +1,61 @@
+use git2::Repository;
+use std::{fs, path::PathBuf};
+
+use crab_hooks::{
+    adopt::adopt_hooks, hook_types::HookTypes, shim, sqllite::SqlLiteConfig, yml_parser,
+};
+
+// Changes the working directory, so it is the only test in this file
+#[test]
+fn test_adopt_hooks() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    let repo = dir.path().canonicalize()?;
+    let hooks_dir = repo.join(".git/hooks");
+    fs::create_dir_all(&hooks_dir)?;
+    let script = "#!/bin/sh\necho legacy\n";
+    fs::write(hooks_dir.join("pre-commit"), script)?;
+    fs::write(hooks_dir.join("pre-commit.sample"), script)?;
+    let pre_push_shim = shim::shim_contents("/usr/bin/crab-hooks", &HookTypes::PrePush, false);
+    fs::write(hooks_dir.join("pre-push"), &pre_push_shim)?;
+
+    let store = tempfile::tempdir()?;
+    let config_file = store.path().join("config.yml");
+    let sql = SqlLiteConfig::new(":memory:")?;
+    adopt_hooks(&config_file, store.path(), &["**".into()], &sql)?;
+
+    // Only the script was adopted, samples and our own shim are left alone
+    let name = format!("{}-pre-commit", repo.file_name().unwrap().to_string_lossy());
+    let config = yml_parser::read_config(&config_file)?;
+    assert_eq!(config.hooks.len(), 1);
+    let hook = &config.hooks[0];
+    assert_eq!(hook.name, name);
+    assert_eq!(hook.glob_pattern, vec!["**".to_string()]);
+    assert!(hook.pass_args);
+
+    let copied = PathBuf::from(&hook.command.cmd);
+    assert_eq!(copied, store.path().join("scripts").join(&name));
+    assert_eq!(fs::read_to_string(&copied)?, script);
+
+    let repo_str = repo.to_string_lossy();
+    assert_eq!(
+        sql.list_repo_hooks(&repo_str, &HookTypes::PreCommit)?,
+        vec![name]
+    );
+    assert!(sql
+        .list_repo_hooks(&repo_str, &HookTypes::PrePush)?
+        .is_empty());
+    assert!(shim::is_shim(&fs::read_to_string(
+        hooks_dir.join("pre-commit")
+    )?));
+    assert_eq!(
+        fs::read_to_string(hooks_dir.join("pre-push"))?,
+        pre_push_shim
+    );
+    assert_eq!(
+        fs::read_to_string(hooks_dir.join("pre-commit.sample"))?,
+        script
+    );
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use git2::Repository;
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use crab_hooks::{
+    backup,
+    git_hook::ApplyOptions,
+    hook_types::HookTypes,
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+};
+
+mod common;
+use common::hook;
+
+// Changes the working directory, so it is the only test in this file
+#[test]
+fn test_force_backs_up_and_restore_puts_back() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    let repo = dir.path().canonicalize()?;
+    let repo_str = repo.to_string_lossy().to_string();
+    let hook_type = HookTypes::PreCommit;
+    let hook_path = shim::hook_path(&repo, &hook_type);
+    let script = "#!/bin/sh\necho original\n";
+    fs::create_dir_all(hook_path.parent().unwrap())?;
+    fs::write(&hook_path, script)?;
+    fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o644))?;
+
+    let data_dir = tempfile::tempdir()?;
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("fmt")?;
+    let fmt = hook("fmt", "true", None);
+    let force = ApplyOptions {
+        force: true,
+        data_dir: data_dir.path().to_path_buf(),
+        ..ApplyOptions::default()
+    };
+
+    // --force keeps a copy of the unmanaged hook before installing the shim
+    fmt.apply_hook(&repo, &hook_type, &force, &sql)?;
+    assert!(shim::is_shim(&fs::read_to_string(&hook_path)?));
+    let backups = sql.list_backups(&repo_str, &hook_type)?;
+    assert_eq!(backups.len(), 1);
+    assert!(backups[0]
+        .path
+        .starts_with(&*data_dir.path().to_string_lossy()));
+    assert_eq!(fs::read_to_string(&backups[0].path)?, script);
+
+    // Restoring is refused while crab-hooks still manages the hook type
+    assert!(backup::restore(&hook_type, None, true, &sql).is_err());
+
+    // Removing the last hook leaves no hook file, the backup is what remove-hook
+    // points at
+    fmt.remove_hook(&repo, &hook_type, &ShimOptions::default(), &sql)?;
+    assert!(!hook_path.exists());
+    assert_eq!(sql.list_backups(&repo_str, &hook_type)?, backups);
+
+    // Backups taken within the same second, or of repos whose paths only differ
+    // in `/` and `_`, are kept apart
+    let other = dir.path().join("other-hook");
+    fs::write(&other, script)?;
+    let backup = |repo: &str| backup::backup_hook(&other, repo, &hook_type, data_dir.path(), &sql);
+    let (first, second) = (backup("/src/a_b/c")?, backup("/src/a_b/c")?);
+    assert_ne!(first, second);
+    assert_ne!(backup("/src/a/b_c")?.parent(), first.parent());
+
+    backup::restore(&hook_type, None, true, &sql)?;
+    assert_eq!(fs::read_to_string(&hook_path)?, script);
+    assert_eq!(
+        fs::metadata(&hook_path)?.permissions().mode() & 0o777,
+        0o755
+    );
+    Ok(())
+}
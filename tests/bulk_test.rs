@@ -0,0 +1,73 @@
+use std::{fs, path::PathBuf};
+
+use crab_hooks::{
+    bulk::{for_each_repo, resolve_repos},
+    hook_types::HookTypes,
+    sqllite::SqlLiteConfig,
+};
+
+#[test]
+fn test_resolve_repos_from_all_sources() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    for name in ["a", "b", "c"] {
+        fs::create_dir(root.join(name))?;
+    }
+    let list = root.join("repos.txt");
+    fs::write(
+        &list,
+        format!(
+            "# team repos\n{}\n\n{}\n",
+            root.join("b").display(),
+            root.join("a").display()
+        ),
+    )?;
+
+    let config = SqlLiteConfig::new(":memory:")?;
+    config.register_repo("/known/registered", 1)?;
+    config.add_hook_to_repo("fmt", "/known/installed", &HookTypes::PreCommit)?;
+
+    let repos = resolve_repos(&[root.join("a")], Some(&list), false, &config)?;
+    assert_eq!(repos, [root.join("a"), root.join("b")]);
+
+    let repos = resolve_repos(&[], None, true, &config)?;
+    assert_eq!(
+        repos,
+        [
+            PathBuf::from("/known/installed"),
+            PathBuf::from("/known/registered")
+        ]
+    );
+
+    // Unknown paths are reported up front
+    assert!(resolve_repos(&[root.join("nope")], None, false, &config).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_for_each_repo_stops_or_continues() {
+    let repos: Vec<PathBuf> = ["/a", "/b", "/c"].iter().map(PathBuf::from).collect();
+    let fail_b = |repo: &std::path::Path| -> Result<(), Box<dyn std::error::Error>> {
+        if repo == std::path::Path::new("/b") {
+            Err("boom".into())
+        } else {
+            Ok(())
+        }
+    };
+
+    let mut seen = Vec::new();
+    let result = for_each_repo(&repos, false, |repo| {
+        seen.push(repo.to_path_buf());
+        fail_b(repo)
+    });
+    assert!(result.is_err());
+    assert_eq!(seen.len(), 2);
+
+    let mut seen = Vec::new();
+    let result = for_each_repo(&repos, true, |repo| {
+        seen.push(repo.to_path_buf());
+        fail_b(repo)
+    });
+    assert!(result.is_err());
+    assert_eq!(seen.len(), 3);
+}
@@ -0,0 +1,96 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{
+    cache,
+    git_hook::{GitHook, RunOptions, RunOutcome},
+    hook_types::HookTypes,
+    sqllite::SqlLiteConfig,
+};
+
+mod common;
+use common::hook;
+
+// Changes the working directory, so it is the only test in this file
+#[test]
+fn test_cached_passes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    fs::write(dir.path().join("main.rs"), "fn main() {}")?;
+    fs::write(dir.path().join("notes.txt"), "a")?;
+
+    let rust = GitHook {
+        glob_pattern: vec!["*.rs".into()],
+        ..hook("check", "true", None)
+    };
+    let first = cache::content_key(&repo, &rust)?;
+    fs::write(dir.path().join("notes.txt"), "b")?;
+    assert_eq!(cache::content_key(&repo, &rust)?, first);
+    fs::write(dir.path().join("main.rs"), "fn main() { }")?;
+    assert_ne!(cache::content_key(&repo, &rust)?, first);
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("check")?;
+    let cached = RunOptions {
+        use_cache: true,
+        ..RunOptions::default()
+    };
+    assert_eq!(rust.run(&[], None, &cached, &sql)?, RunOutcome::Passed);
+    assert_eq!(rust.run(&[], None, &cached, &sql)?, RunOutcome::Cached);
+    assert_eq!(
+        rust.run(&[], None, &RunOptions::default(), &sql)?,
+        RunOutcome::Passed
+    );
+
+    // A different command is a different cache entry, a new description is not
+    let mut changed = rust.clone();
+    changed.description = Some("changed".into());
+    assert_eq!(changed.run(&[], None, &cached, &sql)?, RunOutcome::Cached);
+    changed.command.args = Some("--all".into());
+    assert_eq!(changed.run(&[], None, &cached, &sql)?, RunOutcome::Passed);
+
+    fs::write(dir.path().join("main.rs"), "fn main() {}")?;
+    assert_eq!(rust.run(&[], None, &cached, &sql)?, RunOutcome::Passed);
+
+    // So are other arguments, stdin or hook type
+    let args = vec!["origin".to_string()];
+    assert_eq!(rust.run(&args, None, &cached, &sql)?, RunOutcome::Passed);
+    assert_eq!(rust.run(&args, None, &cached, &sql)?, RunOutcome::Cached);
+    let other_args = vec!["upstream".to_string()];
+    assert_eq!(
+        rust.run(&other_args, None, &cached, &sql)?,
+        RunOutcome::Passed
+    );
+    assert_eq!(
+        rust.run(&args, Some(b"refs/heads/main"), &cached, &sql)?,
+        RunOutcome::Passed
+    );
+
+    // A commit-msg hook is keyed by the message, its file is always the same
+    let message = GitHook {
+        pass_args: true,
+        ..hook("msg", "grep", Some("-q GOOD"))
+    };
+    sql.add_hook("msg")?;
+    let commit_msg = RunOptions {
+        hook_type: Some(HookTypes::CommitMsg),
+        use_cache: true,
+    };
+    let message_file = vec![".git/COMMIT_EDITMSG".to_string()];
+    fs::write(dir.path().join(".git/COMMIT_EDITMSG"), "GOOD two\n")?;
+    assert_eq!(
+        message.run(&message_file, None, &commit_msg, &sql)?,
+        RunOutcome::Passed
+    );
+    assert_eq!(
+        message.run(&message_file, None, &commit_msg, &sql)?,
+        RunOutcome::Cached
+    );
+    fs::write(dir.path().join(".git/COMMIT_EDITMSG"), "bad message\n")?;
+    assert!(message.run(&message_file, None, &commit_msg, &sql).is_err());
+
+    assert_eq!(sql.clear_cached_passes()?, 7);
+    assert_eq!(rust.run(&[], None, &cached, &sql)?, RunOutcome::Passed);
+    Ok(())
+}
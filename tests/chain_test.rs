@@ -0,0 +1,64 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{
+    git_hook::ApplyOptions,
+    hook_types::HookTypes,
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+};
+
+mod common;
+use common::hook;
+
+#[test]
+fn test_chain_keeps_and_restores_the_legacy_hook() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    let repo = dir.path().canonicalize()?;
+    let hook_type = HookTypes::PreCommit;
+    let hook_path = shim::hook_path(&repo, &hook_type);
+    let legacy_path = shim::legacy_hook_path(&repo, &hook_type);
+    let script = "#!/bin/sh\necho legacy\n";
+    fs::create_dir_all(hook_path.parent().unwrap())?;
+    fs::write(&hook_path, script)?;
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("fmt")?;
+    sql.add_hook("lint")?;
+    let chain = ApplyOptions {
+        chain: true,
+        ..ApplyOptions::default()
+    };
+
+    // Without --chain or --force the unmanaged hook is left alone
+    assert!(hook("fmt", "true", None)
+        .apply_hook(&repo, &hook_type, &ApplyOptions::default(), &sql)
+        .is_err());
+    assert_eq!(fs::read_to_string(&hook_path)?, script);
+
+    // --chain moves it aside and installs the shim
+    hook("fmt", "true", None).apply_hook(&repo, &hook_type, &chain, &sql)?;
+    assert_eq!(fs::read_to_string(&legacy_path)?, script);
+    assert!(shim::is_shim(&fs::read_to_string(&hook_path)?));
+    hook("lint", "true", None).apply_hook(&repo, &hook_type, &chain, &sql)?;
+    assert_eq!(fs::read_to_string(&legacy_path)?, script);
+
+    // The legacy hook comes back once the last managed hook is removed
+    hook("fmt", "true", None).remove_hook(&repo, &hook_type, &ShimOptions::default(), &sql)?;
+    assert!(shim::is_shim(&fs::read_to_string(&hook_path)?));
+    assert!(legacy_path.exists());
+    hook("lint", "true", None).remove_hook(&repo, &hook_type, &ShimOptions::default(), &sql)?;
+    assert_eq!(fs::read_to_string(&hook_path)?, script);
+    assert!(!legacy_path.exists());
+
+    // An existing .legacy is never overwritten
+    fs::write(&legacy_path, "#!/bin/sh\necho older\n")?;
+    assert!(hook("fmt", "true", None)
+        .apply_hook(&repo, &hook_type, &chain, &sql)
+        .is_err());
+    assert_eq!(fs::read_to_string(&hook_path)?, script);
+    assert_eq!(fs::read_to_string(&legacy_path)?, "#!/bin/sh\necho older\n");
+    assert!(!sql.check_if_hook_is_known(&repo.to_string_lossy(), &hook_type)?);
+    Ok(())
+}
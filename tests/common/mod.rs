@@ -0,0 +1,15 @@
+use crab_hooks::git_hook::{CommandConfig, GitHook};
+
+/// A hook called `name` running `cmd` with `args` on every file
+pub fn hook(name: &str, cmd: &str, args: Option<&str>) -> GitHook {
+    GitHook {
+        name: name.into(),
+        command: CommandConfig {
+            cmd: cmd.into(),
+            args: args.map(Into::into),
+            ..CommandConfig::default()
+        },
+        glob_pattern: vec!["**".into()],
+        ..GitHook::default()
+    }
+}
@@ -0,0 +1,67 @@
+use crab_hooks::{
+    disable::{self, is_disabled},
+    hook_types::HookTypes,
+    sqllite::SqlLiteConfig,
+    time::{self, format_time, parse_until},
+};
+
+#[test]
+fn test_parse_until() {
+    assert_eq!(parse_until("30m", 1000), Ok(1000 + 30 * 60));
+    assert_eq!(parse_until("1d", 0), Ok(86400));
+    assert_eq!(parse_until("2w", 0), Ok(14 * 86400));
+    assert_eq!(parse_until("1970-01-02", 0), Ok(86400));
+    assert_eq!(parse_until("2000-03-01", 0), Ok(951868800));
+    let at = parse_until("2026-10-19T18:30", 0).unwrap();
+    assert_eq!(format_time(at), "2026-10-19 18:30 UTC");
+    assert!(parse_until("tomorrow", 0).is_err());
+    assert!(parse_until("2026-13-01", 0).is_err());
+    assert!(parse_until("2026-02-31", 0).is_err());
+    assert!(parse_until("2026-04-31", 0).is_err());
+    assert!(parse_until("2026-02-29", 0).is_err());
+    assert!(parse_until("2028-02-29", 0).is_ok());
+    assert!(parse_until("2100-02-29", 0).is_err());
+    assert!(parse_until("2000-02-29", 0).is_ok());
+    assert!(parse_until("2026-01-01T-1:00", 0).is_err());
+}
+
+#[test]
+fn test_disable_and_enable() -> Result<(), Box<dyn std::error::Error>> {
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let repo = std::path::Path::new("/tmp/repo");
+    sql.add_hook("check")?;
+    sql.add_hook_to_repo("check", "/tmp/repo", &HookTypes::PrePush)?;
+    sql.add_hook_to_repo("check", "/tmp/repo", &HookTypes::PreCommit)?;
+    assert!(!is_disabled("/tmp/repo", "check", None, &sql)?);
+
+    // One type only
+    disable::disable(repo, "check", Some(&HookTypes::PrePush), None, &sql)?;
+    assert!(is_disabled(
+        "/tmp/repo",
+        "check",
+        Some(&HookTypes::PrePush),
+        &sql
+    )?);
+    assert!(!is_disabled(
+        "/tmp/repo",
+        "check",
+        Some(&HookTypes::PreCommit),
+        &sql
+    )?);
+    assert!(!is_disabled("/tmp/repo", "check", None, &sql)?);
+
+    // Every type, until a time that has passed
+    sql.set_hook_disabled("/tmp/repo", "check", None, true, Some(time::now() - 1))?;
+    assert!(!is_disabled("/tmp/repo", "check", None, &sql)?);
+    // which can't be asked for
+    let past = parse_until("2001-01-01", time::now()).unwrap();
+    assert!(disable::disable(repo, "check", None, Some(past), &sql).is_err());
+    assert!(!is_disabled("/tmp/repo", "check", None, &sql)?);
+    disable::disable(repo, "check", None, Some(time::now() + 3600), &sql)?;
+    assert!(is_disabled("/tmp/repo", "check", None, &sql)?);
+
+    disable::enable(repo, "check", None, &sql)?;
+    assert!(!is_disabled("/tmp/repo", "check", None, &sql)?);
+    assert!(disable::disable(repo, "other", None, None, &sql).is_err());
+    Ok(())
+}
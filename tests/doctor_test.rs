@@ -0,0 +1,103 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{
+    doctor::{apply_fix, check_database, check_hook_names, check_repo, Fix},
+    hook_types::HookTypes,
+    shim::{self, ShimOptions},
+    sqllite::{SqlLiteConfig, SCHEMA_VERSION},
+    yml_parser::Config,
+};
+
+#[test]
+fn test_doctor_checks_and_fixes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = fs::canonicalize(dir.path())?;
+    let git_repo = Repository::init(&repo)?;
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let repo_str = repo.to_str().unwrap();
+
+    sql.add_hook("check")?;
+    sql.add_hook_to_repo("check", repo_str, &HookTypes::PrePush)?;
+    shim::write_shim(&repo, &HookTypes::PrePush, &ShimOptions::default(), &sql)?;
+    assert!(check_repo(&repo, &sql)?.is_empty());
+
+    // A hand-written script in place of the shim
+    let hook_file = shim::hook_path(&repo, &HookTypes::PrePush);
+    fs::write(&hook_file, "#!/bin/sh\nexit 0\n")?;
+    let problems = check_repo(&repo, &sql)?;
+    assert_eq!(problems.len(), 1);
+    let fix = problems[0].fix.clone().unwrap();
+    assert_eq!(
+        fix,
+        Fix::RewriteShim {
+            repo: repo.clone(),
+            hook_type: HookTypes::PrePush
+        }
+    );
+    let data_dir = tempfile::tempdir()?;
+    apply_fix(&fix, &ShimOptions::default(), data_dir.path(), &sql)?;
+    assert!(check_repo(&repo, &sql)?.is_empty());
+    assert_eq!(sql.list_backups(repo_str, &HookTypes::PrePush)?.len(), 1);
+
+    // core.hooksPath pointing elsewhere
+    git_repo.config()?.set_str("core.hooksPath", ".githooks")?;
+    let problems = check_repo(&repo, &sql)?;
+    assert_eq!(problems.len(), 1);
+    apply_fix(
+        problems[0].fix.as_ref().unwrap(),
+        &ShimOptions::default(),
+        data_dir.path(),
+        &sql,
+    )?;
+    assert!(check_repo(&repo, &sql)?.is_empty());
+
+    // A hook that is gone from the config
+    let problems = check_hook_names(&Config::default(), &sql)?;
+    assert_eq!(problems.len(), 1);
+    apply_fix(
+        problems[0].fix.as_ref().unwrap(),
+        &ShimOptions::default(),
+        data_dir.path(),
+        &sql,
+    )?;
+    assert!(sql.list_all_repo_hooks()?.is_empty());
+    assert!(!hook_file.exists());
+    Ok(())
+}
+
+#[test]
+fn test_database_is_only_migrated_by_the_fix() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("data/hooks.db");
+
+    // A missing database is reported, not created
+    let (sql, problems) = check_database(&db_path);
+    assert!(sql.is_none());
+    assert_eq!(problems.len(), 1);
+    assert!(!db_path.exists());
+
+    // So is one from before the migrations, which is left as it is
+    fs::create_dir_all(db_path.parent().unwrap())?;
+    fs::write(&db_path, "")?;
+    let (sql, problems) = check_database(&db_path);
+    assert!(sql.is_none());
+    let fix = problems[0].fix.clone().unwrap();
+    assert_eq!(
+        fix,
+        Fix::MigrateDatabase {
+            path: db_path.clone()
+        }
+    );
+    assert_eq!(
+        SqlLiteConfig::open_read_only(db_path.to_str().unwrap())?.schema_version()?,
+        0
+    );
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    apply_fix(&fix, &ShimOptions::default(), dir.path(), &sql)?;
+    let (sql, problems) = check_database(&db_path);
+    assert!(problems.is_empty());
+    assert_eq!(sql.unwrap().schema_version()?, SCHEMA_VERSION);
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use git2::Repository;
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use crab_hooks::{
+    git_hook::{GitHook, RunOptions, RunOutcome},
+    history,
+    hook_types::HookTypes,
+    sqllite::{RunFilter, SqlLiteConfig},
+    stats,
+};
+
+mod common;
+use common::hook;
+
+// Changes the working directory, so it is the only test in this file
+#[test]
+fn test_flaky_hook_is_retried() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    fs::write(dir.path().join("a.txt"), "a")?;
+
+    // Fails on every odd call, kept outside the repo so the content stays the same
+    let scripts = tempfile::tempdir()?;
+    let script = scripts.path().join("flaky.sh");
+    fs::write(
+        &script,
+        "#!/bin/sh\necho x >> \"$0.calls\"\n[ $(( $(wc -l < \"$0.calls\") % 2 )) -eq 0 ]\n",
+    )?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+
+    let lint = GitHook {
+        retries: Some(2),
+        ..hook("lint", &script.to_string_lossy(), None)
+    };
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("lint")?;
+    let options = RunOptions::default();
+
+    // Not known to be flaky yet, so the failure stands
+    assert!(lint.run(&[], None, &options, &sql).is_err());
+    assert!(!history::is_flaky(&lint, &sql)?);
+    assert_eq!(lint.run(&[], None, &options, &sql)?, RunOutcome::Passed);
+    assert!(history::is_flaky(&lint, &sql)?);
+
+    // Now the failure is retried, and both attempts are recorded
+    assert_eq!(lint.run(&[], None, &options, &sql)?, RunOutcome::Passed);
+    let runs = sql.list_runs(&RunFilter::default())?;
+    let attempts: Vec<(String, i64)> = runs
+        .iter()
+        .map(|r| (r.outcome.clone(), r.attempt))
+        .collect();
+    assert_eq!(
+        attempts,
+        vec![
+            ("pass".to_string(), 2),
+            ("fail".to_string(), 1),
+            ("pass".to_string(), 1),
+            ("fail".to_string(), 1),
+        ]
+    );
+    assert!(runs.iter().all(|r| r.content_key == runs[0].content_key));
+
+    // Passing on one commit message and failing on another is not flaky, git
+    // hands a commit-msg hook the same file path with different messages
+    let message = GitHook {
+        retries: Some(2),
+        pass_args: true,
+        ..hook("message", "grep", Some("-q GOOD"))
+    };
+    sql.add_hook("message")?;
+    let commit_msg = RunOptions {
+        hook_type: Some(HookTypes::CommitMsg),
+        ..RunOptions::default()
+    };
+    let message_file = vec![".git/COMMIT_EDITMSG".to_string()];
+    fs::write(dir.path().join(".git/COMMIT_EDITMSG"), "GOOD one\n")?;
+    assert_eq!(
+        message.run(&message_file, None, &commit_msg, &sql)?,
+        RunOutcome::Passed
+    );
+    fs::write(dir.path().join(".git/COMMIT_EDITMSG"), "bad one\n")?;
+    assert!(message.run(&message_file, None, &commit_msg, &sql).is_err());
+    assert!(!history::is_flaky(&message, &sql)?);
+    let runs = sql.list_runs(&RunFilter {
+        hook: Some("message".into()),
+        ..RunFilter::default()
+    })?;
+    let stats = stats::compute(&runs, 0, 5);
+    assert_eq!(stats.by_hook[0].flaky_inputs, 0);
+    assert_eq!(stats.by_hook[0].flakiness, None);
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use git2::Repository;
+
+use crab_hooks::{
+    git_hook::{GitHook, RunOptions, RunOutcome},
+    history::{self, RunStatus},
+    hook_types::HookTypes,
+    sqllite::{RunFilter, RunRecord, SqlLiteConfig},
+    time,
+    yml_parser::HistoryRetention,
+};
+
+mod common;
+use common::hook;
+
+fn record(hook: &str, repo: &str, started_at: i64, outcome: RunStatus) -> RunRecord {
+    RunRecord {
+        id: 0,
+        hook: hook.into(),
+        repo: Some(repo.into()),
+        hook_type: Some("pre-commit".into()),
+        head: None,
+        started_at,
+        duration_ms: 10,
+        exit_code: Some(0),
+        signal: None,
+        outcome: outcome.to_string(),
+        config_hash: None,
+        content_key: None,
+        attempt: 1,
+    }
+}
+
+#[test]
+fn test_filters_and_retention() -> Result<(), Box<dyn std::error::Error>> {
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let now = time::now();
+    sql.add_run(&record("fmt", "/a", now - 100 * 86400, RunStatus::Pass))?;
+    sql.add_run(&record("fmt", "/a", now - 3600, RunStatus::Fail))?;
+    sql.add_run(&record("clippy", "/b", now - 60, RunStatus::Pass))?;
+    sql.add_run(&record("fmt", "/b", now, RunStatus::Skip))?;
+
+    let all = sql.list_runs(&RunFilter::default())?;
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[0].outcome, "skip");
+
+    let fmt_in_a = sql.list_runs(&RunFilter {
+        hook: Some("fmt".into()),
+        repo: Some("/a".into()),
+        ..RunFilter::default()
+    })?;
+    assert_eq!(fmt_in_a.len(), 2);
+    let recent = RunFilter {
+        since: Some(time::parse_since("2h", now)?),
+        ..RunFilter::default()
+    };
+    assert_eq!(sql.list_runs(&recent)?.len(), 3);
+    let failed = RunFilter {
+        outcome: Some("fail".into()),
+        ..RunFilter::default()
+    };
+    assert_eq!(sql.list_runs(&failed)?.len(), 1);
+
+    // The 100 day old run is past the default 90 days
+    assert_eq!(
+        history::apply_retention(&HistoryRetention::default(), &sql)?,
+        1
+    );
+    let small = HistoryRetention {
+        keep_days: 90,
+        max_runs: 2,
+    };
+    assert_eq!(history::apply_retention(&small, &sql)?, 1);
+    let left: Vec<String> = sql
+        .list_runs(&RunFilter::default())?
+        .into_iter()
+        .map(|r| r.hook)
+        .collect();
+    assert_eq!(left, vec!["fmt", "clippy"]);
+    Ok(())
+}
+
+// Changes the working directory, so it is the only test in this file doing that
+#[test]
+fn test_runs_are_recorded() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    std::fs::write(dir.path().join("a.txt"), "a")?;
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let options = RunOptions {
+        hook_type: Some(HookTypes::PrePush),
+        use_cache: false,
+    };
+    for name in ["true", "false", "sleep"] {
+        sql.add_hook(name)?;
+    }
+    assert_eq!(
+        hook("true", "true", None).run(&[], None, &options, &sql)?,
+        RunOutcome::Passed
+    );
+    assert!(hook("false", "false", None)
+        .run(&[], None, &options, &sql)
+        .is_err());
+    // Input the command never reads does not hold up the timeout
+    let input = vec![b'x'; 1 << 20];
+    let sleep = GitHook {
+        timeout: Some(1),
+        ..hook("sleep", "sleep", Some("5"))
+    };
+    assert!(sleep.run(&[], Some(&input), &options, &sql).is_err());
+
+    let runs = sql.list_runs(&RunFilter::default())?;
+    let outcome = |name: &str| runs.iter().find(|r| r.hook == name).cloned().unwrap();
+    assert_eq!(outcome("true").outcome, "pass");
+    assert_eq!(outcome("true").hook_type.as_deref(), Some("pre-push"));
+    assert_eq!(
+        outcome("true").repo,
+        Some(dir.path().canonicalize()?.to_string_lossy().to_string())
+    );
+    assert_eq!(outcome("false").outcome, "fail");
+    assert_eq!(outcome("false").exit_code, Some(1));
+    let timed_out = outcome("sleep");
+    assert_eq!(timed_out.outcome, "timeout");
+    assert!(timed_out.duration_ms >= 1000 && timed_out.duration_ms < 5000);
+
+    // git's arguments only reach commands that ask for them
+    sql.add_hook("test")?;
+    let push_args = vec!["origin".to_string(), "git@example.com:repo".to_string()];
+    let mut empty = hook("test", "test", Some("-z"));
+    assert_eq!(
+        empty.run(&push_args, None, &options, &sql)?,
+        RunOutcome::Passed
+    );
+    empty.pass_args = true;
+    assert!(empty.run(&push_args, None, &options, &sql).is_err());
+    Ok(())
+}
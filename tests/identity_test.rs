@@ -0,0 +1,97 @@
+use git2::{Repository, Signature};
+use std::fs;
+
+use crab_hooks::{
+    git_hook::ApplyOptions,
+    hook_types::HookTypes,
+    identity::{self, find_previous_location, relocate},
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+};
+
+mod common;
+use common::hook;
+
+fn init_repo(path: &std::path::Path) -> Result<Repository, Box<dyn std::error::Error>> {
+    let repo = Repository::init(path)?;
+    {
+        let signature = Signature::now("test", "test@example.com")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+    }
+    repo.remote("origin", "https://example.com/acme/app.git")?;
+    Ok(repo)
+}
+
+#[test]
+fn test_identity_survives_moves() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    let old = root.join("old");
+    let new = root.join("new");
+    let git_repo = init_repo(&old)?;
+    let root_commit = git_repo.head()?.peel_to_commit()?.id().to_string();
+
+    let identity = identity::identify(&old)?;
+    assert_eq!(identity.root_commit.as_deref(), Some(root_commit.as_str()));
+    assert_eq!(
+        identity.remote.as_deref(),
+        Some("https://example.com/acme/app.git")
+    );
+
+    // Applying through a symlink records the real path
+    let link = root.join("link");
+    std::os::unix::fs::symlink(&old, &link)?;
+    let sql = SqlLiteConfig::new(":memory:")?;
+    hook("check", "true", None).apply_hook(
+        &link,
+        &HookTypes::PrePush,
+        &ApplyOptions::default(),
+        &sql,
+    )?;
+    let old_str = old.to_string_lossy().to_string();
+    assert_eq!(sql.list_installs()?, [(old_str.clone(), "pre-push".into())]);
+
+    // Nothing moved yet
+    assert_eq!(find_previous_location(&old, &sql)?, None);
+
+    fs::remove_file(&link)?;
+    fs::rename(&old, &new)?;
+    assert_eq!(find_previous_location(&new, &sql)?, Some(old.clone()));
+
+    relocate(&old, &new, &ShimOptions::default(), &sql)?;
+    let new_str = new.to_string_lossy().to_string();
+    assert_eq!(sql.list_installs()?, [(new_str.clone(), "pre-push".into())]);
+    assert_eq!(sql.list_repo_identities()?[0].0, new_str);
+    let content = fs::read_to_string(shim::hook_path(&new, &HookTypes::PrePush))?;
+    assert!(shim::is_shim(&content));
+    assert_eq!(find_previous_location(&new, &sql)?, None);
+    Ok(())
+}
+
+#[test]
+fn test_old_rows_get_canonical_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    let real = root.join("real");
+    fs::create_dir(&real)?;
+    let link = root.join("link");
+    std::os::unix::fs::symlink(&real, &link)?;
+    let db = root.join("hooks.db");
+    let db_str = db.to_str().unwrap();
+
+    // Rows written through the symlink, by a version before the paths were canonicalized
+    let sql = SqlLiteConfig::new(db_str)?;
+    sql.add_hook("check")?;
+    sql.add_hook_to_repo("check", link.to_str().unwrap(), &HookTypes::PrePush)?;
+    sql.add_hook_to_repo("check", "/no/such/repo", &HookTypes::PrePush)?;
+    drop(sql);
+    sqlite::open(&db)?.execute("PRAGMA user_version = 8")?;
+
+    let sql = SqlLiteConfig::new(db_str)?;
+    assert_eq!(
+        sql.list_known_repos()?,
+        ["/no/such/repo", real.to_str().unwrap()]
+    );
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use git2::Repository;
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use crab_hooks::{
+    git_hook::{GitHook, RunOptions},
+    hook_types::HookTypes,
+    logs::{self, Captured, LOG_LIMIT},
+    sqllite::SqlLiteConfig,
+};
+
+mod common;
+use common::hook;
+
+fn script_hook(dir: &Path, name: &str, body: &str) -> Result<GitHook, Box<dyn std::error::Error>> {
+    let script = dir.join(name);
+    fs::write(&script, format!("#!/bin/sh\n{}\n", body))?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+    Ok(hook(name, &script.to_string_lossy(), None))
+}
+
+// Changes the working directory, so it is the only test in this file doing that
+#[test]
+fn test_failed_output_is_kept() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+    fs::write(dir.path().join("a.txt"), "a")?;
+    let scripts = tempfile::tempdir()?;
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let options = RunOptions {
+        hook_type: Some(HookTypes::PreCommit),
+        use_cache: false,
+    };
+    let passing = script_hook(scripts.path(), "passing", "echo all good")?;
+    let failing = script_hook(
+        scripts.path(),
+        "failing",
+        "echo checking\necho 'src/main.rs: error' >&2\nexit 3",
+    )?;
+    for hook in [&passing, &failing] {
+        sql.add_hook(&hook.name)?;
+    }
+
+    logs::start_last_run("pre-commit")?;
+    passing.run(&[], None, &options, &sql)?;
+    assert!(failing.run(&[], None, &options, &sql).is_err());
+    assert!(sql.list_run_logs(Some("passing"), 10)?.is_empty());
+
+    let failed = sql.list_run_logs(Some("failing"), 10)?;
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].run.exit_code, Some(3));
+    let output = String::from_utf8(logs::decompress(&failed[0].output)?)?;
+    assert!(output.contains("checking\n"));
+    assert!(output.contains("src/main.rs: error\n"));
+    assert!(!failed[0].truncated);
+
+    let last_run = fs::read_to_string(dir.path().join(".git/crab-hooks/last-run.log"))?;
+    assert!(last_run.starts_with("crab-hooks pre-commit, "));
+    assert!(last_run.contains("passing pre-commit: pass, exit 0 ==\nall good\n"));
+    assert!(last_run.contains("failing pre-commit: fail, exit 3 =="));
+
+    Ok(())
+}
+
+#[test]
+fn test_output_is_capped() -> Result<(), Box<dyn std::error::Error>> {
+    let mut captured = Captured::default();
+    captured.push(&vec![b'x'; LOG_LIMIT]);
+    assert!(!captured.truncated);
+    captured.push(b"last line\n");
+    assert!(captured.truncated);
+    assert_eq!(captured.output.len(), LOG_LIMIT);
+    assert!(captured.output.ends_with(b"xlast line\n"));
+
+    let compressed = logs::compress(&captured.output)?;
+    assert!(compressed.len() < LOG_LIMIT / 10);
+    assert_eq!(logs::decompress(&compressed)?, captured.output);
+    Ok(())
+}
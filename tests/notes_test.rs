@@ -0,0 +1,69 @@
+use git2::{Repository, Signature};
+
+use crab_hooks::{
+    hook_types::HookTypes,
+    notes::{missing_hooks, passed_on_head, read_notes, record, verify},
+};
+
+mod common;
+use common::hook;
+
+#[test]
+fn test_notes_record_and_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = Repository::init(dir.path())?;
+    let signature = Signature::now("test", "test@example.com")?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let commit = repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+
+    let check = hook("check", "true", None);
+    assert!(!passed_on_head(dir.path(), &check));
+    assert_eq!(missing_hooks(&repo, commit, &["check".into()])?, ["check"]);
+
+    // pre-commit results land on the staged tree, which the commit shares
+    record(dir.path(), &HookTypes::PreCommit, &[&check])?;
+    let notes = read_notes(&repo, tree.id());
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].hook, "check");
+    assert_eq!(notes[0].hook_type, "pre-commit");
+    assert!(missing_hooks(&repo, commit, &["check".into()])?.is_empty());
+    assert!(passed_on_head(dir.path(), &check));
+
+    // A changed config is not proven by the old result
+    assert!(!passed_on_head(
+        dir.path(),
+        &hook("check", "true", Some("--all"))
+    ));
+
+    // Recording again replaces the result instead of adding one
+    record(dir.path(), &HookTypes::PreCommit, &[&check])?;
+    assert_eq!(read_notes(&repo, tree.id()).len(), 1);
+
+    record(
+        dir.path(),
+        &HookTypes::PrePush,
+        &[&hook("clippy", "true", None)],
+    )?;
+    assert_eq!(read_notes(&repo, commit)[0].hook, "clippy");
+
+    verify(dir.path(), "HEAD", &["check".into(), "clippy".into()])?;
+    assert!(verify(dir.path(), "HEAD", &["fmt".into()]).is_err());
+
+    // Staged changes are what pre-commit notes, unstaged ones mean the hooks
+    // did not run on the staged tree
+    std::fs::write(dir.path().join("a.txt"), "a")?;
+    let mut index = repo.index()?;
+    index.add_path(std::path::Path::new("a.txt"))?;
+    index.write()?;
+    let staged = index.write_tree()?;
+    record(dir.path(), &HookTypes::PreCommit, &[&check])?;
+    assert_eq!(read_notes(&repo, staged).len(), 1);
+
+    std::fs::write(dir.path().join("a.txt"), "changed")?;
+    let fmt = hook("fmt", "true", None);
+    record(dir.path(), &HookTypes::PreCommit, &[&fmt])?;
+    assert_eq!(read_notes(&repo, staged).len(), 1);
+    record(dir.path(), &HookTypes::PrePush, &[&fmt])?;
+    assert!(read_notes(&repo, commit).iter().all(|n| n.hook != "fmt"));
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use git2::Repository;
+use std::path::Path;
+
+use crab_hooks::{
+    git_hook::ApplyOptions, hook_types::HookTypes, profile::apply_profile, sqllite::SqlLiteConfig,
+    yml_parser::Config,
+};
+
+const CONFIG: &str = r#"
+hooks:
+  - name: "check"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**/*.rs"]
+  - name: "clippy"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**/*.rs"]
+  - name: "fmt"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**/*.rs"]
+profiles:
+  rust:
+    pre-push: ["check", "clippy"]
+    pre-commit: ["fmt"]
+"#;
+
+#[test]
+fn test_apply_and_update_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = dir.path().join("repo");
+    Repository::init(&repo)?;
+    let repo_str = repo.to_str().unwrap();
+
+    let mut config: Config = serde_yaml::from_str(CONFIG)?;
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let options = ApplyOptions::default();
+
+    // fmt was already installed by hand, the profile does not take it over
+    config.hooks[2].apply_hook(&repo, &HookTypes::PreCommit, &options, &sql)?;
+
+    apply_profile(&repo, "rust", &config, &options, &sql)?;
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["check", "clippy"]
+    );
+    assert!(Path::new(&repo).join(".git/hooks/pre-push").exists());
+    assert_eq!(sql.list_profile_repos("rust")?, [repo_str]);
+    assert_eq!(sql.list_profile_hooks(repo_str, "rust")?.len(), 2);
+
+    // Applying again changes nothing
+    apply_profile(&repo, "rust", &config, &options, &sql)?;
+    assert_eq!(sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?.len(), 2);
+
+    // clippy and fmt leave the profile: only the hook the profile installed goes
+    let profile = config.profiles.get_mut("rust").unwrap();
+    profile.insert("pre-push".into(), vec!["check".into()]);
+    profile.remove("pre-commit");
+    apply_profile(&repo, "rust", &config, &options, &sql)?;
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["check"]
+    );
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PreCommit)?,
+        ["fmt"]
+    );
+
+    assert!(apply_profile(&repo, "nope", &config, &options, &sql).is_err());
+    Ok(())
+}
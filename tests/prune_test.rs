@@ -0,0 +1,114 @@
+use git2::{Repository, Signature};
+use std::fs;
+
+use crab_hooks::{
+    hook_types::HookTypes,
+    identity,
+    prune::{find_moved, find_stale, prune, Stale},
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+    yml_parser::Config,
+};
+
+#[test]
+fn test_prune() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    let kept = root.join("kept");
+    let edited = root.join("edited");
+    Repository::init(&kept)?;
+    Repository::init(&edited)?;
+    let gone = root.join("gone").to_string_lossy().to_string();
+    let (kept_str, edited_str) = (kept.to_str().unwrap(), edited.to_str().unwrap());
+
+    let config: Config = serde_yaml::from_str(
+        r#"
+hooks:
+  - name: "check"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+"#,
+    )?;
+    let sql = SqlLiteConfig::new(":memory:")?;
+    for name in ["check", "removed"] {
+        sql.add_hook(name)?;
+    }
+    sql.add_hook_to_repo("check", kept_str, &HookTypes::PrePush)?;
+    shim::write_shim(&kept, &HookTypes::PrePush, &ShimOptions::default(), &sql)?;
+    sql.add_hook_to_repo("check", edited_str, &HookTypes::PreCommit)?;
+    fs::write(
+        shim::hook_path(&edited, &HookTypes::PreCommit),
+        "#!/bin/sh\n",
+    )?;
+    sql.add_hook_to_repo("removed", &gone, &HookTypes::PrePush)?;
+    sql.register_repo(&gone, 1)?;
+
+    let stale = find_stale(&config, &sql)?;
+    assert_eq!(
+        stale,
+        [
+            Stale::Repo(gone.clone()),
+            Stale::HookType {
+                repo: edited_str.to_string(),
+                hook_type: "pre-commit".into()
+            },
+            Stale::Hook("removed".into()),
+        ]
+    );
+
+    prune(&config, true, &sql)?;
+    assert_eq!(find_stale(&config, &sql)?.len(), 3);
+
+    prune(&config, false, &sql)?;
+    assert!(find_stale(&config, &sql)?.is_empty());
+    assert_eq!(
+        sql.list_installs()?,
+        [(kept_str.to_string(), "pre-push".into())]
+    );
+    assert!(sql.list_registered_repos()?.is_empty());
+    assert_eq!(sql.list_hook_names()?, ["check"]);
+    Ok(())
+}
+
+#[test]
+fn test_prune_keeps_moved_repos() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    let moved = root.join("moved");
+    let repo = Repository::init(&moved)?;
+    let signature = Signature::now("test", "test@example.com")?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])?;
+    let (old, moved_str) = (
+        root.join("old").to_string_lossy().to_string(),
+        moved.to_string_lossy().to_string(),
+    );
+
+    // The checkout was moved from `old`, the new path is known but not relocated yet
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("check")?;
+    sql.add_hook_to_repo("check", &old, &HookTypes::PreCommit)?;
+    sql.set_repo_identity(&old, &identity::identify(&moved)?)?;
+    sql.register_repo(&moved_str, 1)?;
+
+    let config: Config = serde_yaml::from_str(
+        r#"
+hooks:
+  - name: "check"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+"#,
+    )?;
+    assert_eq!(find_moved(&sql)?, [(old.clone(), moved_str.clone())]);
+    assert!(find_stale(&config, &sql)?.is_empty());
+    prune(&config, false, &sql)?;
+
+    // Relocating still finds the old rows
+    assert_eq!(
+        identity::find_previous_location(&moved, &sql)?,
+        Some(root.join("old"))
+    );
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{
+    cache::CacheKey,
+    git_hook::{added_ids, adopt_id, rename_hook, ApplyOptions},
+    hook_types::HookTypes,
+    shim::{self, ShimOptions},
+    sqllite::SqlLiteConfig,
+    yml_parser::{read_config, write_config},
+};
+
+const CONFIG: &str = r#"
+shim_template: |
+  #!/usr/bin/env sh
+  # hooks: {{hook_names}}
+  {{crab_hooks}}
+hooks:
+  - name: "check"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+  - name: "fmt"
+    id: "fmt-1"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+profiles:
+  rust:
+    pre-push: ["check", "fmt"]
+"#;
+
+#[test]
+fn test_rename_hook() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let config_file = dir.path().join("config.yml");
+    fs::write(&config_file, CONFIG)?;
+    let repo = fs::canonicalize(dir.path())?.join("repo");
+    Repository::init(&repo)?;
+    let repo_str = repo.to_str().unwrap();
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let config = read_config(&config_file)?;
+    let options = ApplyOptions {
+        shim: ShimOptions::load(&config_file)?,
+        ..ApplyOptions::default()
+    };
+    for name in ["check", "fmt"] {
+        let hook = config.find_hook(name).unwrap();
+        hook.apply_hook(&repo, &HookTypes::PrePush, &options, &sql)?;
+    }
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["check", "fmt-1"]
+    );
+
+    // Without an id the database rows and the script follow the new name
+    rename_hook(&config_file, "check", "lint", &sql)?;
+    let config = read_config(&config_file)?;
+    assert!(config.find_hook("check").is_none());
+    assert!(config.find_hook("lint").is_some());
+    assert_eq!(config.profiles["rust"]["pre-push"], ["lint", "fmt"]);
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["lint", "fmt-1"]
+    );
+    let script = fs::read_to_string(shim::hook_path(&repo, &HookTypes::PrePush))?;
+    assert!(script.contains("# hooks: lint fmt-1"));
+    // The rewritten script still hands over the config it was installed from
+    assert!(script.contains(&format!("--config-file \"{}\"", config_file.display())));
+
+    // With an id only the config changes
+    rename_hook(&config_file, "fmt", "format", &sql)?;
+    let config = read_config(&config_file)?;
+    assert_eq!(config.find_hook("format").unwrap().key(), "fmt-1");
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["lint", "fmt-1"]
+    );
+
+    assert!(rename_hook(&config_file, "lint", "format", &sql).is_err());
+    assert!(rename_hook(&config_file, "nope", "other", &sql).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_id_added_after_install() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let config_file = dir.path().join("config.yml");
+    fs::write(&config_file, CONFIG)?;
+    let repo = fs::canonicalize(dir.path())?.join("repo");
+    Repository::init(&repo)?;
+    let repo_str = repo.to_str().unwrap();
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    let shim = ShimOptions::load(&config_file)?;
+    let options = ApplyOptions {
+        shim: shim.clone(),
+        ..ApplyOptions::default()
+    };
+    let mut config = read_config(&config_file)?;
+    let hook = config.find_hook("check").unwrap().clone();
+    hook.apply_hook(&repo, &HookTypes::PrePush, &options, &sql)?;
+    let key = CacheKey {
+        repo: repo_str.into(),
+        config_hash: hook.config_hash()?,
+        content_key: "tree".into(),
+        input: "none".into(),
+    };
+    sql.add_cached_pass("check", &key, 0)?;
+    assert!(added_ids(&config, &sql)?.is_empty());
+
+    // Only what the hook runs is hashed, so the id and description keep its results
+    let edited = config.hooks.iter_mut().find(|h| h.name == "check").unwrap();
+    edited.id = Some("check-1".into());
+    edited.description = Some("Runs cargo check".into());
+    assert_eq!(edited.config_hash()?, key.config_hash);
+    write_config(&config_file, &config)?;
+
+    let config = read_config(&config_file)?;
+    let added = added_ids(&config, &sql)?;
+    assert_eq!(added.len(), 1);
+    adopt_id(&added[0].name, added[0].key(), &shim, &sql)?;
+    assert!(added_ids(&config, &sql)?.is_empty());
+    assert_eq!(
+        sql.list_repo_hooks(repo_str, &HookTypes::PrePush)?,
+        ["check-1"]
+    );
+    assert!(sql.is_cached_pass("check-1", &key)?);
+    assert!(!sql.is_cached_pass("check", &key)?);
+    let script = fs::read_to_string(shim::hook_path(&repo, &HookTypes::PrePush))?;
+    assert!(script.contains("# hooks: check-1"));
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::fs;
+
+use crab_hooks::{
+    hook_types::HookTypes, repos::inventory, shim::shim_contents, sqllite::SqlLiteConfig,
+};
+
+#[test]
+fn test_inventory_groups_and_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = dir.path().join("repo");
+    fs::create_dir_all(repo.join(".git/hooks"))?;
+    fs::write(
+        repo.join(".git/hooks/pre-commit"),
+        shim_contents("/usr/bin/crab-hooks", &HookTypes::PreCommit, false),
+    )?;
+    // pre-push was replaced by hand
+    fs::write(repo.join(".git/hooks/pre-push"), "#!/bin/sh\necho hi\n")?;
+    let repo = repo.to_str().unwrap();
+    let gone = dir.path().join("gone");
+    let gone = gone.to_str().unwrap();
+
+    let config = SqlLiteConfig::new(":memory:")?;
+    config.add_hook_to_repo("fmt", repo, &HookTypes::PreCommit)?;
+    config.add_hook_to_repo("check", repo, &HookTypes::PrePush)?;
+    config.add_hook_to_repo("clippy", repo, &HookTypes::PrePush)?;
+    config.add_hook_to_repo("fmt", gone, &HookTypes::PreCommit)?;
+
+    let repos = inventory(None, None, &config)?;
+    assert_eq!(repos.len(), 2);
+
+    let gone_repo = repos.iter().find(|r| r.path == gone).unwrap();
+    assert!(!gone_repo.exists);
+    assert!(!gone_repo.is_healthy());
+
+    let repo = repos.iter().find(|r| r.path == repo).unwrap();
+    assert!(repo.exists);
+    assert_eq!(repo.hook_types.len(), 2);
+    assert_eq!(repo.hook_types[0].hook_type, "pre-commit");
+    assert!(repo.hook_types[0].script_ok);
+    assert_eq!(repo.hook_types[1].hooks, ["check", "clippy"]);
+    assert!(!repo.hook_types[1].script_ok);
+
+    // Filters
+    let repos = inventory(Some("clippy"), None, &config)?;
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0].hook_types[0].hooks, ["clippy"]);
+    let repos = inventory(None, Some(&HookTypes::PreCommit), &config)?;
+    assert_eq!(repos.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{hook_types::HookTypes, scan::find_repos, shim::shim_contents};
+
+#[test]
+fn test_find_repos_classifies_and_skips() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+
+    Repository::init(root.join("plain"))?;
+    Repository::init(root.join("work/unmanaged"))?;
+    fs::write(
+        root.join("work/unmanaged/.git/hooks/pre-commit"),
+        "#!/bin/sh\n",
+    )?;
+    Repository::init(root.join("managed"))?;
+    fs::write(
+        root.join("managed/.git/hooks/pre-push"),
+        shim_contents("/usr/bin/crab-hooks", &HookTypes::PrePush, false),
+    )?;
+    // Skipped folders
+    Repository::init(root.join(".hidden/repo"))?;
+    Repository::init(root.join("node_modules/dep"))?;
+    Repository::init(root.join("project/target/repo"))?;
+    // Too deep
+    Repository::init(root.join("a/b/c/deep"))?;
+
+    let repos = find_repos(root, 3);
+    let names: Vec<_> = repos
+        .iter()
+        .map(|r| r.path.strip_prefix(root).unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["managed", "plain", "work/unmanaged"]);
+
+    // Sample hooks do not count
+    assert!(repos[1].managed.is_empty() && repos[1].unmanaged.is_empty());
+    assert_eq!(repos[0].managed, ["pre-push"]);
+    assert_eq!(repos[2].unmanaged, ["pre-commit"]);
+
+    assert_eq!(find_repos(root, 4).len(), 4);
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::{fs, os::unix::fs::PermissionsExt, process::Command};
+
+use crab_hooks::{
+    hook_types::HookTypes,
+    shim::{
+        is_shim, render_shim, shim_contents, shim_version, validate_template, ShimContext,
+        SHIM_VERSION, SHIM_VERSION_ENV,
+    },
+};
+use std::path::Path;
+
+#[test]
+fn test_shim_dispatches_hook_type() {
+    let content = shim_contents("/usr/bin/crab-hooks", &HookTypes::PrePush, false);
+    assert!(content.starts_with("#!/usr/bin/env sh\n"));
+    assert!(content.contains("dispatch pre-push \"$@\""));
+    // Prefer PATH, fall back to the recorded binary
+    assert!(content.contains("command -v crab-hooks"));
+    assert!(content.contains("CRAB_HOOKS_BIN=\"/usr/bin/crab-hooks\""));
+    assert!(!content.contains(".legacy"));
+    assert!(is_shim(&content));
+    assert_eq!(shim_version(&content), Some(SHIM_VERSION));
+}
+
+#[test]
+fn test_chained_shim_runs_legacy_first() {
+    let content = shim_contents("/usr/bin/crab-hooks", &HookTypes::CommitMsg, true);
+    let legacy = content
+        .find("commit-msg.legacy")
+        .expect("legacy hook should be called");
+    let dispatch = content
+        .find("dispatch commit-msg")
+        .expect("dispatch should be called");
+    assert!(legacy < dispatch);
+}
+
+#[test]
+fn test_unmanaged_script_is_not_a_shim() {
+    assert!(!is_shim("#!/bin/sh\necho hello\n"));
+    assert_eq!(shim_version("#!/bin/sh\necho hello\n"), None);
+}
+
+#[test]
+fn test_shim_falls_back_to_recorded_binary() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    // Stand-in for the binary that echoes its arguments
+    let fake_bin = dir.path().join("fake-crab-hooks");
+    fs::write(&fake_bin, "#!/bin/sh\necho \"$@\"\n")?;
+    fs::set_permissions(&fake_bin, fs::Permissions::from_mode(0o755))?;
+
+    let hook = dir.path().join("pre-commit");
+    let content = shim_contents(fake_bin.to_str().unwrap(), &HookTypes::PreCommit, false);
+    fs::write(&hook, content)?;
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+
+    // crab-hooks is not on this PATH
+    let output = Command::new(&hook)
+        .arg("one")
+        .env("PATH", "/usr/bin:/bin")
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "dispatch pre-commit one\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_shim_handles_missing_binary() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let hook = dir.path().join("pre-commit");
+    let missing = dir.path().join("uninstalled");
+    let content = shim_contents(missing.to_str().unwrap(), &HookTypes::PreCommit, false);
+    fs::write(&hook, content)?;
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+
+    // Skipped with a warning by default
+    let output = Command::new(&hook).env("PATH", "/usr/bin:/bin").output()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("binary not found"));
+
+    let output = Command::new(&hook)
+        .env("PATH", "/usr/bin:/bin")
+        .env("CRAB_HOOKS_MISSING", "fail")
+        .output()?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn test_render_custom_template() {
+    let template = "#!/usr/bin/env bash\nsource ~/.nvm/nvm.sh\necho {{hook_type}} {{hook_names}} {{repo_root}}\n{{crab_hooks}}\n";
+    let names = vec!["check".to_string(), "clippy".to_string()];
+    let content = render_shim(
+        template,
+        &ShimContext {
+            exe: "/usr/bin/crab-hooks",
+            hook_type: &HookTypes::PrePush,
+            chained: false,
+            hook_names: &names,
+            repo_root: Path::new("/repo"),
+            config_file: Some(Path::new("/home/me/hooks.yml")),
+        },
+    );
+
+    // The template shebang is kept, the marker and version follow it
+    let mut lines = content.lines();
+    assert_eq!(lines.next(), Some("#!/usr/bin/env bash"));
+    assert!(is_shim(&content));
+    assert_eq!(shim_version(&content), Some(SHIM_VERSION));
+    assert!(content.contains("source ~/.nvm/nvm.sh\necho pre-push check clippy /repo\n"));
+    // dispatch reads the config the hooks were installed from
+    assert!(content.contains(
+        "exec \"$CRAB_HOOKS_BIN\" --config-file \"/home/me/hooks.yml\" dispatch pre-push \"$@\""
+    ));
+}
+
+#[test]
+fn test_render_template_calling_dispatch() {
+    let content = render_shim(
+        "#!/bin/sh\n{{binary}} dispatch {{hook_type}} \"$@\"\n",
+        &ShimContext {
+            exe: "/usr/bin/crab-hooks",
+            hook_type: &HookTypes::PreCommit,
+            chained: false,
+            hook_names: &[],
+            repo_root: Path::new("/repo"),
+            config_file: None,
+        },
+    );
+
+    // dispatch learns the shim version without `{{crab_hooks}}` too
+    let export = format!("export {}={}", SHIM_VERSION_ENV, SHIM_VERSION);
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[3], export);
+    assert_eq!(lines[4], "/usr/bin/crab-hooks dispatch pre-commit \"$@\"");
+}
+
+#[test]
+fn test_validate_template() {
+    assert!(validate_template("{{crab_hooks}}").is_ok());
+    assert!(validate_template(
+        "export PATH=/opt/bin:$PATH\n{{binary}} dispatch {{hook_type}} \"$@\""
+    )
+    .is_ok());
+    // Does not hand over to crab-hooks
+    assert!(validate_template("#!/bin/sh\necho {{hook_type}}\n").is_err());
+    // Unknown variable
+    assert!(validate_template("{{crab_hooks}} {{nope}}").is_err());
+    assert!(validate_template("{{crab_hooks}} {{").is_err());
+}
@@ -0,0 +1,72 @@
+use git2::Repository;
+
+use crab_hooks::{
+    dispatch::validate_runnable,
+    git_hook::{RunOptions, RunOutcome},
+    hook_types::HookTypes,
+    sqllite::{HookCounts, SqlLiteConfig},
+    yml_parser,
+};
+
+mod common;
+use common::hook;
+
+// Changes the environment and the working directory, so it is the only test in this file
+#[test]
+fn test_requested_skips() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let repo = Repository::init(dir.path())?;
+    std::env::set_current_dir(dir.path())?;
+
+    let check = hook("check", "true", None);
+    let sql = SqlLiteConfig::new(":memory:")?;
+    sql.add_hook("check")?;
+    assert_eq!(check.requested_skip(), None);
+
+    std::env::set_var("CRAB_HOOKS_SKIP", "clippy, check");
+    assert!(matches!(
+        check.run(&[], None, &RunOptions::default(), &sql)?,
+        RunOutcome::Skipped(_)
+    ));
+    std::env::set_var("CRAB_HOOKS_SKIP", "clippy");
+    assert_eq!(check.requested_skip(), None);
+    std::env::remove_var("CRAB_HOOKS_SKIP");
+
+    std::env::set_var("CRAB_HOOKS", "0");
+    assert_eq!(check.requested_skip(), Some("CRAB_HOOKS=0".into()));
+    std::env::remove_var("CRAB_HOOKS");
+
+    // A skipped hook is not validated, so skipping gets past a broken command
+    let config = dir.path().join("config.yml");
+    std::fs::write(
+        &config,
+        "- name: \"broken\"\n  command:\n    cmd: \"crab-hooks-no-such-command\"\n  glob_pattern: [\"**\"]\n",
+    )?;
+    let broken = yml_parser::read_file(config.clone())?;
+    let repo_str = dir.path().canonicalize()?.to_string_lossy().to_string();
+    let validate = || validate_runnable(&config, &repo_str, &HookTypes::PreCommit, &broken, &sql);
+    assert!(validate().is_err());
+    std::env::set_var("CRAB_HOOKS_SKIP", "broken");
+    assert!(validate().is_ok());
+    std::env::remove_var("CRAB_HOOKS_SKIP");
+    std::env::set_var("CRAB_HOOKS", "0");
+    assert!(validate().is_ok());
+    std::env::remove_var("CRAB_HOOKS");
+
+    repo.config()?
+        .set_multivar("crabhooks.skip", "^$", "lint")?;
+    assert_eq!(check.requested_skip(), None);
+    repo.config()?
+        .set_multivar("crabhooks.skip", "^$", "fmt,check")?;
+    assert!(check.requested_skip().is_some());
+
+    assert_eq!(
+        sql.hook_counts("check")?,
+        Some(HookCounts {
+            total_runs: 0,
+            successful_runs: 0,
+            skipped_runs: 1,
+        })
+    );
+    Ok(())
+}
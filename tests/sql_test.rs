@@ -2,7 +2,7 @@
 // Ensure your `SqlLiteConfig` struct exposes `connection` or provides a getter for stats.
 // Replace `your_crate` with the name in Cargo.toml under `[package].name`.
 use crab_hooks::hook_types::HookTypes;
-use crab_hooks::sqllite::SqlLiteConfig;
+use crab_hooks::sqllite::{SqlLiteConfig, SCHEMA_VERSION};
 
 #[test]
 fn test_add_and_check_hook() -> Result<(), Box<dyn std::error::Error>> {
@@ -54,3 +54,113 @@ fn test_run_count_paths() -> Result<(), Box<dyn std::error::Error>> {
     config.add_failed_run("nonexistent")?;
     Ok(())
 }
+
+#[test]
+fn test_backup_listing() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SqlLiteConfig::new(":memory:")?;
+    let hook_type = HookTypes::PreCommit;
+
+    assert!(config.list_backups("repo1", &hook_type)?.is_empty());
+
+    let first = config.add_backup("repo1", &hook_type, "/backups/a", 10)?;
+    let second = config.add_backup("repo1", &hook_type, "/backups/b", 20)?;
+    config.add_backup("repo2", &hook_type, "/backups/c", 30)?;
+    config.add_backup("repo1", &HookTypes::PrePush, "/backups/d", 40)?;
+
+    // Only the matching repo and type, newest first
+    let backups = config.list_backups("repo1", &hook_type)?;
+    assert_eq!(backups.len(), 2);
+    assert_eq!(backups[0].id, second);
+    assert_eq!(backups[0].path, "/backups/b");
+    assert_eq!(backups[1].id, first);
+    Ok(())
+}
+
+#[test]
+fn test_list_repo_hooks_in_install_order() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SqlLiteConfig::new(":memory:")?;
+    let hook_type = HookTypes::PrePush;
+
+    for name in ["b", "a", "c"] {
+        config.add_hook(name)?;
+        config.add_hook_to_repo(name, "repo1", &hook_type)?;
+    }
+    config.add_hook_to_repo("d", "repo1", &HookTypes::PreCommit)?;
+    config.add_hook_to_repo("e", "repo2", &hook_type)?;
+
+    assert_eq!(
+        config.list_repo_hooks("repo1", &hook_type)?,
+        ["b", "a", "c"]
+    );
+
+    config.remove_hook("repo1", &hook_type, "a")?;
+    assert_eq!(config.list_repo_hooks("repo1", &hook_type)?, ["b", "c"]);
+
+    // One entry per repo and hook type
+    assert_eq!(
+        config.list_installs()?,
+        [
+            ("repo1".to_string(), "pre-commit".to_string()),
+            ("repo1".to_string(), "pre-push".to_string()),
+            ("repo2".to_string(), "pre-push".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_register_repos() -> Result<(), Box<dyn std::error::Error>> {
+    let config = SqlLiteConfig::new(":memory:")?;
+    assert!(config.list_registered_repos()?.is_empty());
+
+    config.register_repo("/b", 1)?;
+    config.register_repo("/a", 2)?;
+    // Registering twice is ignored
+    config.register_repo("/b", 3)?;
+    assert_eq!(config.list_registered_repos()?, ["/a", "/b"]);
+    Ok(())
+}
+
+#[test]
+fn test_schema_is_migrated() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("hooks.db");
+    let path = path.to_str().unwrap();
+
+    let config = SqlLiteConfig::new(path)?;
+    assert_eq!(config.schema_version()?, SCHEMA_VERSION);
+    config.add_hook_to_repo("hook1", "repo1", &HookTypes::PreCommit)?;
+    drop(config);
+
+    // Opening again keeps the data and does not migrate twice
+    let config = SqlLiteConfig::new(path)?;
+    assert_eq!(config.schema_version()?, SCHEMA_VERSION);
+    assert!(config.check_if_hook_is_known("repo1", &HookTypes::PreCommit)?);
+    Ok(())
+}
+
+#[test]
+fn test_old_database_is_upgraded() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("hooks.db");
+    let path = path.to_str().unwrap();
+
+    // Tables as created before migrations existed
+    let connection = sqlite::open(path)?;
+    connection.execute(
+        "CREATE TABLE hooks (name TEXT UNIQUE, total_runs INTEGER, succesful_runs INTEGER);
+         CREATE TABLE repo_hooks (name TEXT, repo TEXT, type TEXT, FOREIGN KEY(name) REFERENCES hooks(name));
+         INSERT INTO repo_hooks VALUES ('hook1', 'repo1', 'pre-commit');",
+    )?;
+    drop(connection);
+
+    let config = SqlLiteConfig::new(path)?;
+    assert_eq!(config.schema_version()?, SCHEMA_VERSION);
+    assert_eq!(
+        config.list_repo_hooks("repo1", &HookTypes::PreCommit)?,
+        ["hook1"]
+    );
+    config.set_hook_profile("repo1", &HookTypes::PreCommit, "hook1", "rust")?;
+    assert_eq!(config.list_profile_hooks("repo1", "rust")?.len(), 1);
+    Ok(())
+}
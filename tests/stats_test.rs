@@ -0,0 +1,114 @@
+use crab_hooks::{
+    history::RunStatus,
+    sqllite::RunRecord,
+    stats::{self, GroupStats},
+};
+
+fn run(hook: &str, repo: &str, duration_ms: i64, outcome: RunStatus) -> RunRecord {
+    RunRecord {
+        id: 0,
+        hook: hook.into(),
+        repo: Some(repo.into()),
+        hook_type: Some("pre-push".into()),
+        head: None,
+        started_at: 0,
+        duration_ms,
+        exit_code: None,
+        signal: None,
+        outcome: outcome.to_string(),
+        config_hash: None,
+        content_key: None,
+        attempt: 1,
+    }
+}
+
+#[test]
+fn test_compute_stats() {
+    let mut runs = Vec::new();
+    for ms in 1..=20 {
+        runs.push(run("clippy", "/a", ms * 1000, RunStatus::Pass));
+    }
+    runs.push(run("fmt", "/a", 100, RunStatus::Pass));
+    runs.push(run("fmt", "/b,c", 200, RunStatus::Fail));
+    runs.push(run("fmt", "/b,c", 0, RunStatus::Skip));
+    runs.push(run("test", "/b,c", 5000, RunStatus::Timeout));
+
+    let report = stats::compute(&runs, 0, 2);
+
+    let clippy = &report.by_hook[0];
+    assert_eq!(clippy.name, "clippy");
+    assert_eq!((clippy.runs, clippy.passed), (20, 20));
+    assert_eq!(clippy.p50_ms, Some(10_000));
+    assert_eq!(clippy.p95_ms, Some(19_000));
+    assert_eq!(
+        report.by_hook[1],
+        GroupStats {
+            name: "fmt".into(),
+            runs: 2,
+            passed: 1,
+            failed: 1,
+            skipped: 1,
+            success_rate: Some(0.5),
+            p50_ms: Some(100),
+            p95_ms: Some(200),
+            flaky_inputs: 0,
+            flakiness: None,
+        }
+    );
+
+    let repos: Vec<(&str, usize)> = report
+        .by_repo
+        .iter()
+        .map(|g| (g.name.as_str(), g.runs))
+        .collect();
+    assert_eq!(repos, vec![("/a", 21), ("/b,c", 2)]);
+
+    let slowest: Vec<&str> = report.slowest.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(slowest, vec!["clippy", "test"]);
+    let failing: Vec<&str> = report
+        .most_failing
+        .iter()
+        .map(|g| g.name.as_str())
+        .collect();
+    assert_eq!(failing, vec!["test", "fmt"]);
+
+    let csv = stats::to_csv(&report);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "group,name,runs,passed,failed,skipped,success_rate,p50_ms,p95_ms,flaky_inputs,flakiness"
+    );
+    assert_eq!(lines[2], "hook,fmt,2,1,1,1,0.5000,100,200,0,");
+    assert_eq!(lines[5], "repo,\"/b,c\",2,0,2,1,0.0000,200,5000,0,");
+}
+
+#[test]
+fn test_flakiness() {
+    let on = |content: &str, outcome: RunStatus| RunRecord {
+        content_key: Some(content.into()),
+        config_hash: Some("config".into()),
+        ..run("lint", "/a", 10, outcome)
+    };
+    let runs = vec![
+        on("a", RunStatus::Fail),
+        on("a", RunStatus::Pass),
+        on("b", RunStatus::Pass),
+        on("b", RunStatus::Pass),
+        on("c", RunStatus::Fail),
+        // Another config is another input
+        RunRecord {
+            config_hash: Some("other".into()),
+            ..on("c", RunStatus::Pass)
+        },
+        run("fmt", "/a", 10, RunStatus::Pass),
+    ];
+
+    let report = stats::compute(&runs, 0, 5);
+    let lint = report.by_hook.iter().find(|g| g.name == "lint").unwrap();
+    assert_eq!(lint.flaky_inputs, 1);
+    assert_eq!(lint.flakiness, Some(0.5));
+    let fmt = report.by_hook.iter().find(|g| g.name == "fmt").unwrap();
+    assert_eq!(fmt.flakiness, None);
+    let flaky: Vec<&str> = report.flaky.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(flaky, vec!["lint"]);
+}
@@ -0,0 +1,102 @@
+use git2::Repository;
+use std::fs;
+
+use crab_hooks::{
+    git_hook::ApplyOptions,
+    sqllite::SqlLiteConfig,
+    sync::{plan, sync, Action},
+    yml_parser::Config,
+};
+
+fn config(root: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    let yaml = format!(
+        r#"
+hooks:
+  - name: "check"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+  - name: "fmt"
+    command:
+      cmd: "cargo"
+    glob_pattern: ["**"]
+profiles:
+  rust:
+    pre-push: ["check"]
+repos:
+  - path: "{}/by-path/*"
+    hooks:
+      pre-commit: ["fmt"]
+  - remote: "*github.com*acme/*"
+    profiles: ["rust"]
+"#,
+        root
+    );
+    Ok(serde_yaml::from_str(&yaml)?)
+}
+
+#[test]
+fn test_plan_and_sync() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = fs::canonicalize(dir.path())?;
+    let by_path = root.join("by-path/one");
+    let by_remote = root.join("elsewhere/two");
+    let unmatched = root.join("unmatched");
+    Repository::init(&by_path)?;
+    Repository::init(&by_remote)?.remote("origin", "git@github.com:acme/two.git")?;
+    Repository::init(&unmatched)?;
+
+    let sql = SqlLiteConfig::new(":memory:")?;
+    for repo in [&by_path, &by_remote, &unmatched] {
+        sql.register_repo(repo.to_str().unwrap(), 1)?;
+    }
+    let mut config = config(root.to_str().unwrap())?;
+
+    let plans = plan(&config, &sql)?;
+    assert_eq!(plans.len(), 2);
+    let path_plan = plans.iter().find(|p| p.repo == by_path).unwrap();
+    assert_eq!(
+        path_plan.actions,
+        [Action::Install {
+            hook_type: "pre-commit".into(),
+            name: "fmt".into()
+        }]
+    );
+    let remote_plan = plans.iter().find(|p| p.repo == by_remote).unwrap();
+    assert_eq!(
+        remote_plan.actions,
+        [Action::Install {
+            hook_type: "pre-push".into(),
+            name: "check".into()
+        }]
+    );
+
+    sync(&config, &ApplyOptions::default(), false, false, &sql)?;
+    assert!(plan(&config, &sql)?.is_empty());
+
+    // A hook file deleted by hand is drift
+    fs::remove_file(by_path.join(".git/hooks/pre-commit"))?;
+    let plans = plan(&config, &sql)?;
+    assert_eq!(
+        plans[0].actions,
+        [Action::RewriteShim {
+            hook_type: "pre-commit".into()
+        }]
+    );
+    sync(&config, &ApplyOptions::default(), false, false, &sql)?;
+    assert!(by_path.join(".git/hooks/pre-commit").exists());
+
+    // Hooks no longer declared are removed
+    config.repos[0].hooks.clear();
+    let plans = plan(&config, &sql)?;
+    assert_eq!(
+        plans[0].actions,
+        [Action::Remove {
+            hook_type: "pre-commit".into(),
+            name: "fmt".into()
+        }]
+    );
+    sync(&config, &ApplyOptions::default(), false, false, &sql)?;
+    assert!(!by_path.join(".git/hooks/pre-commit").exists());
+    Ok(())
+}
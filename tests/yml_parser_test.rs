@@ -0,0 +1,181 @@
+use crab_hooks::yml_parser::{
+    read_config, test_config, test_hooks, test_profile, write_config, ConfigError,
+};
+use std::io::Write;
+
+const CONFIG: &str = r#"
+- name: "good"
+  command:
+    cmd: "sh"
+  glob_pattern: ["**"]
+- name: "bad"
+  command:
+    cmd: "crab-hooks-no-such-command"
+  glob_pattern: []
+"#;
+
+#[test]
+fn test_only_touched_hooks_are_validated() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(CONFIG.as_bytes())?;
+    let path = file.path().to_path_buf();
+
+    // The broken hook does not block the good one
+    assert!(test_hooks(path.clone(), &["good"]).is_ok());
+
+    let err = test_hooks(path.clone(), &["bad"]).expect_err("bad hook should fail");
+    match err.downcast_ref::<ConfigError>() {
+        Some(ConfigError::Invalid(errors)) => assert_eq!(errors.len(), 2),
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+
+    // Validating everything still fails
+    let err = test_config(path).expect_err("config should fail");
+    assert!(err.is::<ConfigError>());
+    Ok(())
+}
+
+#[test]
+fn test_unparsable_config_is_a_config_error() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(b"- name: [")?;
+
+    let err = test_config(file.path().to_path_buf()).expect_err("config should fail");
+    assert!(matches!(
+        err.downcast_ref::<ConfigError>(),
+        Some(ConfigError::Unreadable(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_type_mistake_is_reported_with_its_location() -> Result<(), Box<dyn std::error::Error>> {
+    let hook = "- name: \"check\"\n  command:\n    cmd: \"sh\"\n  glob_pattern: \"**\"\n";
+    for (text, line) in [(hook.to_string(), 4), (format!("hooks:\n{}", hook), 5)] {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(text.as_bytes())?;
+        let err = read_config(file.path())
+            .expect_err("config should fail")
+            .to_string();
+        assert!(
+            err.contains("glob_pattern: invalid type: string \"**\", expected a sequence"),
+            "{}",
+            err
+        );
+        assert!(err.contains(&format!("line {}", line)), "{}", err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mapping_config_with_shim_template() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("shim.sh"), "#!/bin/bash\n{{crab_hooks}}\n")?;
+    let path = dir.path().join("config.yml");
+    std::fs::write(
+        &path,
+        r#"
+shim_template_file: shim.sh
+hooks:
+  - name: "good"
+    command:
+      cmd: "sh"
+    glob_pattern: ["**"]
+"#,
+    )?;
+
+    let config = read_config(&path)?;
+    assert_eq!(config.hooks.len(), 1);
+    // Relative to the config file
+    assert_eq!(
+        config.load_shim_template()?.as_deref(),
+        Some("#!/bin/bash\n{{crab_hooks}}\n")
+    );
+    test_config(path.clone())?;
+
+    // Settings survive a rewrite
+    write_config(&path, &config)?;
+    assert!(read_config(&path)?.shim_template_file.is_some());
+
+    // A template that never calls crab-hooks is rejected
+    std::fs::write(dir.path().join("shim.sh"), "#!/bin/bash\necho hi\n")?;
+    let err = test_hooks(path, &["good"]).expect_err("template should fail");
+    assert!(err.is::<ConfigError>());
+    Ok(())
+}
+
+#[test]
+fn test_plain_list_stays_a_list() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(CONFIG.as_bytes())?;
+
+    let config = read_config(file.path())?;
+    write_config(file.path(), &config)?;
+    let written = std::fs::read_to_string(file.path())?;
+    assert!(written.starts_with("- name: good"));
+    Ok(())
+}
+
+#[test]
+fn test_hook_ids_must_be_unique() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(
+        br#"
+- name: "fmt"
+  command:
+    cmd: "sh"
+  glob_pattern: ["**"]
+- name: "format"
+  id: "fmt"
+  command:
+    cmd: "sh"
+  glob_pattern: ["**"]
+"#,
+    )?;
+    let err = test_config(file.path().to_path_buf()).expect_err("duplicate key should fail");
+    match err.downcast_ref::<ConfigError>() {
+        Some(ConfigError::Invalid(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].field, "id");
+        }
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_broken_profiles_only_block_themselves() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(
+        br#"
+hooks:
+  - name: "ok"
+    command:
+      cmd: "sh"
+    glob_pattern: ["**"]
+profiles:
+  good:
+    pre-commit: ["ok"]
+  broken:
+    pre-commit: ["typo"]
+repos:
+  - path: "/src/**"
+    hooks:
+      pre-push: ["typo"]
+"#,
+    )?;
+    let path = file.path().to_path_buf();
+
+    assert!(test_hooks(path.clone(), &["ok"]).is_ok());
+    assert!(test_profile(path.clone(), "good").is_ok());
+    let err = test_profile(path.clone(), "broken").expect_err("broken profile should fail");
+    match err.downcast_ref::<ConfigError>() {
+        Some(ConfigError::Invalid(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].field, "profiles.broken.pre-commit");
+        }
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+    assert!(test_config(path).is_err());
+    Ok(())
+}